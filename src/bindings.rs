@@ -0,0 +1,276 @@
+use minifb::{Key, KeyRepeat, Window};
+use std::collections::HashMap;
+use std::fs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    StrafeLeft,
+    StrafeRight,
+    Sprint,
+    TurnLeft,
+    TurnRight,
+    LookUp,
+    LookDown,
+    Jump,
+    Crouch,
+    Interact,
+    ToggleMap,
+    ToggleMusic,
+    ToggleHint,
+    ToggleFisheye,
+    RandomLevel,
+    MenuPrevious,
+    MenuNext,
+    Pause,
+    Retry,
+    Confirm,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 23] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::Sprint,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::LookUp,
+        Action::LookDown,
+        Action::Jump,
+        Action::Crouch,
+        Action::Interact,
+        Action::ToggleMap,
+        Action::ToggleMusic,
+        Action::ToggleHint,
+        Action::ToggleFisheye,
+        Action::RandomLevel,
+        Action::MenuPrevious,
+        Action::MenuNext,
+        Action::Pause,
+        Action::Retry,
+        Action::Confirm,
+        Action::Quit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBack => "move_back",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::Sprint => "sprint",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::LookUp => "look_up",
+            Action::LookDown => "look_down",
+            Action::Jump => "jump",
+            Action::Crouch => "crouch",
+            Action::Interact => "interact",
+            Action::ToggleMap => "toggle_map",
+            Action::ToggleMusic => "toggle_music",
+            Action::ToggleHint => "toggle_hint",
+            Action::ToggleFisheye => "toggle_fisheye",
+            Action::RandomLevel => "random_level",
+            Action::MenuPrevious => "menu_previous",
+            Action::MenuNext => "menu_next",
+            Action::Pause => "pause",
+            Action::Retry => "retry",
+            Action::Confirm => "confirm",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| action.name() == name)
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        match self {
+            Action::MoveForward => vec![Key::W, Key::Up],
+            Action::MoveBack => vec![Key::S, Key::Down],
+            Action::StrafeLeft => vec![Key::A],
+            Action::StrafeRight => vec![Key::D],
+            Action::Sprint => vec![Key::LeftShift],
+            Action::TurnLeft => vec![Key::Left, Key::Q],
+            Action::TurnRight => vec![Key::Right, Key::E],
+            Action::LookUp => vec![Key::PageUp],
+            Action::LookDown => vec![Key::PageDown],
+            Action::Jump => vec![Key::X],
+            Action::Crouch => vec![Key::LeftCtrl, Key::C],
+            Action::Interact => vec![Key::Space],
+            Action::ToggleMap => vec![Key::M],
+            Action::ToggleMusic => vec![Key::T],
+            Action::ToggleHint => vec![Key::H],
+            Action::ToggleFisheye => vec![Key::F],
+            Action::RandomLevel => vec![Key::R],
+            Action::MenuPrevious => vec![Key::Up, Key::Left],
+            Action::MenuNext => vec![Key::Down, Key::Right],
+            Action::Pause => vec![Key::P],
+            Action::Retry => vec![Key::R],
+            Action::Confirm => vec![Key::Enter],
+            Action::Quit => vec![Key::Escape],
+        }
+    }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    let key = match name {
+        "A" => Key::A, "B" => Key::B, "C" => Key::C, "D" => Key::D, "E" => Key::E,
+        "F" => Key::F, "G" => Key::G, "H" => Key::H, "I" => Key::I, "J" => Key::J,
+        "K" => Key::K, "L" => Key::L, "M" => Key::M, "N" => Key::N, "O" => Key::O,
+        "P" => Key::P, "Q" => Key::Q, "R" => Key::R, "S" => Key::S, "T" => Key::T,
+        "U" => Key::U, "V" => Key::V, "W" => Key::W, "X" => Key::X, "Y" => Key::Y,
+        "Z" => Key::Z,
+        "0" => Key::Key0, "1" => Key::Key1, "2" => Key::Key2, "3" => Key::Key3, "4" => Key::Key4,
+        "5" => Key::Key5, "6" => Key::Key6, "7" => Key::Key7, "8" => Key::Key8, "9" => Key::Key9,
+        "Up" => Key::Up, "Down" => Key::Down, "Left" => Key::Left, "Right" => Key::Right,
+        "Space" => Key::Space, "Enter" => Key::Enter, "Escape" => Key::Escape, "Tab" => Key::Tab,
+        "Backspace" => Key::Backspace, "PageUp" => Key::PageUp, "PageDown" => Key::PageDown,
+        "LeftShift" => Key::LeftShift, "RightShift" => Key::RightShift,
+        "LeftCtrl" => Key::LeftCtrl, "RightCtrl" => Key::RightCtrl,
+        "LeftAlt" => Key::LeftAlt, "RightAlt" => Key::RightAlt,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseMode {
+    /// Turn by how far the mouse moved since the last frame. minifb cannot
+    /// capture or recenter the cursor, so turning stops when the hidden
+    /// cursor reaches the edge of the screen.
+    Relative,
+    /// Keep turning while the cursor rests near the left or right edge.
+    Edge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MouseSettings {
+    pub mode: MouseMode,
+    /// Radians per pixel of mouse movement.
+    pub sensitivity: f32,
+    /// Flips looking up and down; turning is never inverted.
+    pub invert: bool,
+}
+
+impl MouseSettings {
+    /// `relative 0.004 false`, as stored in input recordings.
+    pub fn to_line(&self) -> String {
+        let mode = match self.mode {
+            MouseMode::Relative => "relative",
+            MouseMode::Edge => "edge",
+        };
+        format!("{} {} {}", mode, self.sensitivity, self.invert)
+    }
+
+    pub fn parse(line: &str) -> Option<MouseSettings> {
+        let mut fields = line.split_whitespace();
+        let mode = match fields.next()? {
+            "relative" => MouseMode::Relative,
+            "edge" => MouseMode::Edge,
+            _ => return None,
+        };
+        Some(MouseSettings {
+            mode,
+            sensitivity: fields.next()?.parse().ok()?,
+            invert: fields.next()?.parse().ok()?,
+        })
+    }
+}
+
+/// Keys bound to each action plus mouse settings. `controls.cfg` lists
+/// `action = Key, Key` lines; actions it leaves out keep their default (WASD
+/// plus arrows). `mouse_mode`, `mouse_sensitivity` and `mouse_invert` set
+/// the mouse.
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<Key>>,
+    pub mouse: MouseSettings,
+}
+
+impl KeyBindings {
+    pub fn load(path: &str) -> KeyBindings {
+        let mut bindings = KeyBindings {
+            keys: Action::ALL.iter().map(|&action| (action, action.default_keys())).collect(),
+            mouse: MouseSettings {
+                mode: MouseMode::Edge,
+                sensitivity: 0.004,
+                invert: false,
+            },
+        };
+
+        match fs::read_to_string(path) {
+            Ok(config) => bindings.apply_config(&config, path),
+            Err(_) => println!("No se encontró {}, usando controles por defecto", path),
+        }
+
+        bindings
+    }
+
+    fn apply_config(&mut self, config: &str, path: &str) {
+        for (line_number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((name, keys)) = line.split_once('=') else {
+                println!("{}:{}: se esperaba `acción = Tecla, Tecla`", path, line_number + 1);
+                continue;
+            };
+
+            let value = keys.trim();
+            let invalid = || println!("{}:{}: valor inválido `{}`", path, line_number + 1, value);
+            match name.trim() {
+                "mouse_mode" => {
+                    match value {
+                        "relative" => self.mouse.mode = MouseMode::Relative,
+                        "edge" => self.mouse.mode = MouseMode::Edge,
+                        _ => invalid(),
+                    }
+                    continue;
+                }
+                "mouse_sensitivity" => {
+                    match value.parse() {
+                        Ok(sensitivity) => self.mouse.sensitivity = sensitivity,
+                        Err(_) => invalid(),
+                    }
+                    continue;
+                }
+                "mouse_invert" => {
+                    match value.parse() {
+                        Ok(invert) => self.mouse.invert = invert,
+                        Err(_) => invalid(),
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let Some(action) = Action::from_name(name.trim()) else {
+                println!("{}:{}: acción desconocida `{}`", path, line_number + 1, name.trim());
+                continue;
+            };
+
+            let mut parsed = Vec::new();
+            for key in keys.split(',').map(str::trim).filter(|key| !key.is_empty()) {
+                match parse_key(key) {
+                    Some(key) => parsed.push(key),
+                    None => println!("{}:{}: tecla desconocida `{}`", path, line_number + 1, key),
+                }
+            }
+            self.keys.insert(action, parsed);
+        }
+    }
+
+    pub fn is_down(&self, window: &Window, action: Action) -> bool {
+        self.keys[&action].iter().any(|&key| window.is_key_down(key))
+    }
+
+    pub fn is_pressed(&self, window: &Window, action: Action) -> bool {
+        self.keys[&action].iter().any(|&key| window.is_key_pressed(key, KeyRepeat::No))
+    }
+}
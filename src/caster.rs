@@ -1,61 +1,137 @@
 use crate::framebuffer::Framebuffer;
+use crate::maze::{is_open, Maze};
 use crate::player::Player;
 
+/// Face of the hit cell that the ray struck. `North` is the face towards
+/// row 0, `West` the face towards column 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    North,
+    South,
+    East,
+    West,
+}
+
 pub struct Intersect {
     pub distance: f32,
     pub impact: char,
     pub impact_pos: (f32, f32),
+    pub side: Side,
+    /// Column and row of the wall cell that was hit.
+    pub cell: (isize, isize),
+}
+
+impl Intersect {
+    /// Position of the hit along the struck face, in `0.0..=1.0`, measured
+    /// from the hit cell's corner and oriented so textures are never
+    /// mirrored whichever side they are seen from.
+    pub fn wall_offset(&self, block_size: usize) -> f32 {
+        let block_size = block_size as f32;
+        let offset = match self.side {
+            Side::North | Side::South => self.impact_pos.0 - self.cell.0 as f32 * block_size,
+            Side::East | Side::West => self.impact_pos.1 - self.cell.1 as f32 * block_size,
+        };
+        let offset = (offset / block_size).clamp(0.0, 1.0);
+
+        match self.side {
+            Side::North | Side::East => 1.0 - offset,
+            Side::South | Side::West => offset,
+        }
+    }
 }
 
 pub fn cast_ray(
     framebuffer: &mut Framebuffer,
-    maze: &Vec<Vec<char>>,
+    maze: &Maze,
     player: &Player,
     a: f32,
-    block_size: usize,
     draw_line: bool,
 ) -> Intersect {
-    let mut d = 0.0;
-
-    framebuffer.set_current_color(0xFFDDDD);
-
-    loop {
-        let cos = d * a.cos();
-        let sin = d * a.sin();
-
-        
-        let x = (player.pos.x + cos) as usize;
-        let y = (player.pos.y + sin) as usize;
-
-        
-        let i = x / block_size;
-        let j = y / block_size;
-
-        
-        if j >= maze.len() || i >= maze[j].len() {
-            
-            return Intersect {
-                distance: d,
-                impact: ' ', 
-                impact_pos: (player.pos.x + cos, player.pos.y + sin),
-            };
-        }
+    let block = maze.block_size() as f32;
+    let dir_x = a.cos();
+    let dir_y = a.sin();
+
+    let (mut i, mut j) = maze.cell_coords(player.pos);
+
+    let step_i: isize = if dir_x < 0.0 { -1 } else { 1 };
+    let step_j: isize = if dir_y < 0.0 { -1 } else { 1 };
+
+    let delta_x = if dir_x == 0.0 { f32::INFINITY } else { (block / dir_x).abs() };
+    let delta_y = if dir_y == 0.0 { f32::INFINITY } else { (block / dir_y).abs() };
 
-        if maze[j][i] != ' ' && maze[j][i] != 'F' {
-            
-            if d > 0.0 {
-                return Intersect {
-                    distance: d,
-                    impact: maze[j][i],
-                    impact_pos: (player.pos.x + cos, player.pos.y + sin),
-                };
-            }
+    let mut side_x = if dir_x == 0.0 {
+        f32::INFINITY
+    } else if dir_x < 0.0 {
+        (player.pos.x - i as f32 * block) / -dir_x
+    } else {
+        ((i + 1) as f32 * block - player.pos.x) / dir_x
+    };
+    let mut side_y = if dir_y == 0.0 {
+        f32::INFINITY
+    } else if dir_y < 0.0 {
+        (player.pos.y - j as f32 * block) / -dir_y
+    } else {
+        ((j + 1) as f32 * block - player.pos.y) / dir_y
+    };
+
+    let (distance, side, impact) = loop {
+        let (distance, side) = if side_x < side_y {
+            let distance = side_x;
+            side_x += delta_x;
+            i += step_i;
+            (distance, if step_i > 0 { Side::West } else { Side::East })
+        } else {
+            let distance = side_y;
+            side_y += delta_y;
+            j += step_j;
+            (distance, if step_j > 0 { Side::North } else { Side::South })
+        };
+
+        let cell = maze.cell(i, j);
+        if !is_open(cell) {
+            break (distance, side, cell);
         }
+    };
+
+    let impact_pos = (player.pos.x + distance * dir_x, player.pos.y + distance * dir_y);
 
-        if draw_line {
-            framebuffer.point(x, y);
+    if draw_line {
+        framebuffer.set_current_color(0xFFDDDD);
+        for d in 0..distance as usize {
+            let x = player.pos.x + d as f32 * dir_x;
+            let y = player.pos.y + d as f32 * dir_y;
+            framebuffer.point(x as usize, y as usize);
         }
+    }
+
+    Intersect {
+        distance,
+        impact,
+        impact_pos,
+        side,
+        cell: (i, j),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::spawn_player;
+    use crate::maze::parse_maze;
+
+    #[test]
+    fn reports_the_hit_cell_and_side() {
+        let level = parse_maze("+----+\n|@  F|\n+----+", 10).unwrap();
+        let mut player = spawn_player(&level);
+        player.pos.y = 12.5;
+        let mut framebuffer = Framebuffer::new(1, 1);
+
+        let east = cast_ray(&mut framebuffer, &level.maze, &player, 0.0, false);
+        assert_eq!((east.cell, east.side, east.impact), ((5, 1), Side::West, '|'));
+        assert!((east.distance - 35.0).abs() < 1e-4);
+        assert!((east.wall_offset(10) - 0.25).abs() < 1e-4);
 
-        d += 1.0;
+        let north = cast_ray(&mut framebuffer, &level.maze, &player, -std::f32::consts::FRAC_PI_2, false);
+        assert_eq!((north.cell, north.side, north.impact), ((1, 0), Side::South, '-'));
     }
 }
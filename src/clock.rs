@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+/// Measures the time between frames. Each `tick` is clamped to
+/// `MAX_DELTA` so a stall (window dragged, breakpoint) does not turn into
+/// one huge simulation step.
+pub struct GameClock {
+    last_tick: Instant,
+    delta: f32,
+}
+
+impl GameClock {
+    const MAX_DELTA: f32 = 0.1;
+
+    pub fn new() -> GameClock {
+        GameClock {
+            last_tick: Instant::now(),
+            delta: 0.0,
+        }
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        self.delta = now.duration_since(self.last_tick).as_secs_f32().min(Self::MAX_DELTA);
+        self.last_tick = now;
+        self.delta
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+}
+
+/// Splits variable frame time into fixed simulation steps. Whatever is left
+/// over is exposed as `alpha`, the fraction of a step the renderer should
+/// interpolate by.
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+    ticks: u64,
+}
+
+impl FixedTimestep {
+    pub fn new(rate_hz: f32) -> FixedTimestep {
+        FixedTimestep {
+            step: 1.0 / rate_hz,
+            accumulator: 0.0,
+            ticks: 0,
+        }
+    }
+
+    pub fn advance(&mut self, delta: f32) {
+        self.accumulator += delta;
+    }
+
+    /// Consumes one step if enough time has accumulated.
+    pub fn next_step(&mut self) -> bool {
+        if self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            self.ticks += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+
+    /// Simulated time, which unlike wall-clock time only depends on the
+    /// number of steps taken.
+    pub fn time(&self) -> f32 {
+        self.ticks as f32 * self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_stays_within_a_step() {
+        let mut timestep = FixedTimestep::new(60.0);
+        for i in 0..1000 {
+            timestep.advance((i % 7) as f32 * 0.004 + 0.0005);
+            while timestep.next_step() {}
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha = {}", alpha);
+        }
+    }
+}
@@ -1,52 +1,337 @@
-use std::time::Instant;
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::texture::Texture;
 
+/// Horizontal distance from one character to the next, and from one line
+/// to the next, in font pixels.
+const CHAR_ADVANCE: usize = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// `scale` multiplies every font pixel; `max_width`, in screen pixels, wraps
+/// long lines.
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub color: u32,
+    pub scale: usize,
+    pub align: Align,
+    pub max_width: Option<usize>,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            color: 0xFFFFFF,
+            scale: 1,
+            align: Align::Left,
+            max_width: None,
+        }
+    }
+}
+
+/// How a pixel being drawn combines with the one already in the buffer.
+/// In every mode the source's alpha, scaled by the framebuffer's opacity,
+/// decides how much of the effect is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Source over destination.
+    Normal,
+    /// Adds the source to the destination, saturating at white.
+    Additive,
+    /// Darkens the destination by the source, channel by channel.
+    Multiply,
+}
+
+/// A rectangle in pixels. It may start left of or above the origin, so
+/// things partly off screen can still be described.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: isize,
+    pub y: isize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: isize, y: isize, width: usize, height: usize) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn right(&self) -> isize {
+        self.x + self.width as isize
+    }
+
+    fn bottom(&self) -> isize {
+        self.y + self.height as isize
+    }
+
+    fn contains(&self, x: isize, y: isize) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= x || bottom <= y {
+            return Rect::new(self.x, self.y, 0, 0);
+        }
+        Rect::new(x, y, (right - x) as usize, (bottom - y) as usize)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+/// `alpha` decides whether the texture's alpha is honored or every texel is
+/// drawn opaque.
+#[derive(Clone, Copy, Debug)]
+pub struct BlitOptions {
+    pub filter: Filter,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub alpha: bool,
+}
+
+impl Default for BlitOptions {
+    fn default() -> BlitOptions {
+        BlitOptions {
+            filter: Filter::Nearest,
+            flip_x: false,
+            flip_y: false,
+            alpha: true,
+        }
+    }
+}
+
+/// A region of the buffer being drawn into. `origin` is where its local
+/// `(0, 0)` lands in the buffer and `clip` is the part of the buffer it may
+/// touch, both in buffer pixels.
+#[derive(Clone, Copy, Debug)]
+struct Viewport {
+    origin: (isize, isize),
+    width: usize,
+    height: usize,
+    clip: Rect,
+}
+
+/// `width` and `height` are those of the current viewport, so renderers
+/// draw in local coordinates without knowing where they end up.
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
+    stride: usize,
+    viewports: Vec<Viewport>,
     background_color: u32,
     current_color: u32,
-    last_frame_time: Instant,
+    blend_mode: BlendMode,
+    opacity: u32,
+}
 
+fn blend(src: u32, dst: u32, alpha: u32, mode: BlendMode) -> u32 {
+    let channel = |shift: u32| {
+        let s = (src >> shift) & 0xFF;
+        let d = (dst >> shift) & 0xFF;
+        let out = match mode {
+            BlendMode::Normal => (s * alpha + d * (255 - alpha)) / 255,
+            BlendMode::Additive => (d + s * alpha / 255).min(255),
+            BlendMode::Multiply => d * (s * alpha + 255 * (255 - alpha)) / (255 * 255),
+        };
+        out << shift
+    };
+    channel(16) | channel(8) | channel(0)
 }
 
 impl Framebuffer {
-    const DIGITS: [[u8; 5]; 10] = [
-        [0b01110, 0b10001, 0b10001, 0b10001, 0b01110], 
-        [0b00100, 0b01100, 0b00100, 0b00100, 0b01110], 
-        [0b01110, 0b00001, 0b01110, 0b10000, 0b11111], 
-        [0b01110, 0b00001, 0b01110, 0b00001, 0b01110], 
-        [0b10001, 0b10001, 0b11111, 0b00001, 0b00001], 
-        [0b11111, 0b10000, 0b11110, 0b00001, 0b11110], 
-        [0b01110, 0b10000, 0b11110, 0b10001, 0b01110], 
-        [0b11111, 0b00001, 0b00010, 0b00100, 0b01000], 
-        [0b01110, 0b10001, 0b01110, 0b10001, 0b01110], 
-        [0b01110, 0b10001, 0b01111, 0b00001, 0b01110], 
-    ];
     pub fn new(width: usize, height: usize) -> Self {
         Framebuffer {
             width,
             height,
             buffer: vec![0; width * height],
+            stride: width,
+            viewports: vec![Viewport {
+                origin: (0, 0),
+                width,
+                height,
+                clip: Rect::new(0, 0, width, height),
+            }],
             background_color: 0x000000,
             current_color: 0xFFFFFF,
-            last_frame_time: Instant::now(),
+            blend_mode: BlendMode::Normal,
+            opacity: 255,
+        }
+    }
 
+    fn viewport(&self) -> &Viewport {
+        self.viewports.last().expect("la pila de viewports está vacía")
+    }
+
+    /// Makes `rect`, in the current viewport's coordinates, the area drawn
+    /// into until the matching `pop_viewport`. Drawing is clipped to it and
+    /// to every viewport below it.
+    pub fn push_viewport(&mut self, rect: Rect) {
+        let parent = *self.viewport();
+        let origin = (parent.origin.0 + rect.x, parent.origin.1 + rect.y);
+        let bounds = Rect::new(origin.0, origin.1, rect.width, rect.height);
+
+        self.viewports.push(Viewport {
+            origin,
+            width: rect.width,
+            height: rect.height,
+            clip: parent.clip.intersection(&bounds),
+        });
+        self.width = rect.width;
+        self.height = rect.height;
+    }
+
+    pub fn pop_viewport(&mut self) {
+        if self.viewports.len() > 1 {
+            self.viewports.pop();
+            let viewport = *self.viewport();
+            self.width = viewport.width;
+            self.height = viewport.height;
         }
     }
 
+    /// Fills the current viewport with the background color.
     pub fn clear(&mut self) {
-        for pixel in self.buffer.iter_mut() {
-            *pixel = self.background_color;
+        let clip = self.viewport().clip;
+        for y in clip.y..clip.bottom() {
+            let row = y as usize * self.stride;
+            self.buffer[row + clip.x as usize..row + clip.right() as usize].fill(self.background_color);
         }
     }
 
+    /// Draws the current color, which is always opaque; only the opacity
+    /// and blend mode make it see-through.
     pub fn point(&mut self, x: usize, y: usize) {
-        if x < self.width && y < self.height {
-            self.buffer[y * self.width + x] = self.current_color;
+        self.blend_point(x, y, self.current_color | 0xFF000000);
+    }
+
+    /// Draws an ARGB color, such as a texel, honoring its alpha.
+    pub fn blend_point(&mut self, x: usize, y: usize, color: u32) {
+        let viewport = self.viewport();
+        let (x, y) = (x as isize + viewport.origin.0, y as isize + viewport.origin.1);
+        if viewport.clip.contains(x, y) {
+            self.blend_at(y as usize * self.stride + x as usize, color);
+        }
+    }
+
+    fn blend_at(&mut self, index: usize, color: u32) {
+        let alpha = (color >> 24) * self.opacity / 255;
+        let pixel = &mut self.buffer[index];
+        if alpha == 255 && self.blend_mode == BlendMode::Normal {
+            *pixel = color & 0xFFFFFF;
+        } else if alpha > 0 {
+            *pixel = blend(color, *pixel, alpha, self.blend_mode);
+        }
+    }
+
+    /// Whether opaque colors can be written straight into the buffer.
+    fn writes_directly(&self) -> bool {
+        self.blend_mode == BlendMode::Normal && self.opacity == 255
+    }
+
+    /// `rect`, in viewport coordinates, moved to buffer coordinates and cut
+    /// down to what may be drawn.
+    fn visible(&self, rect: Rect) -> Rect {
+        let viewport = self.viewport();
+        let moved = Rect::new(rect.x + viewport.origin.0, rect.y + viewport.origin.1, rect.width, rect.height);
+        viewport.clip.intersection(&moved)
+    }
+
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    /// Global opacity from 0.0 (invisible) to 1.0, applied to everything
+    /// drawn until it is changed back.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = (opacity.clamp(0.0, 1.0) * 255.0).round() as u32;
+    }
+
+    /// Covers the current viewport with the current color, blended.
+    pub fn fill(&mut self) {
+        self.fill_rect(0, 0, self.width, self.height);
+    }
+
+    /// Fills a `width` by `height` rectangle with the current color, clipped
+    /// to the viewport.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let area = self.visible(Rect::new(x as isize, y as isize, width, height));
+        let color = self.current_color | 0xFF000000;
+
+        for y in area.y..area.bottom() {
+            let row = y as usize * self.stride;
+            let span = row + area.x as usize..row + area.right() as usize;
+            if self.writes_directly() {
+                self.buffer[span].fill(color & 0xFFFFFF);
+            } else {
+                for index in span {
+                    self.blend_at(index, color);
+                }
+            }
+        }
+    }
+
+    /// Writes a row of opaque pixels starting at `(x, y)`, clipped to the
+    /// viewport.
+    pub fn copy_row(&mut self, x: usize, y: usize, pixels: &[u32]) {
+        let area = self.visible(Rect::new(x as isize, y as isize, pixels.len(), 1));
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        let skipped = (area.x - (x as isize + self.viewport().origin.0)) as usize;
+        let pixels = &pixels[skipped..skipped + area.width];
+
+        let row = area.y as usize * self.stride;
+        let span = row + area.x as usize..row + area.right() as usize;
+        if self.writes_directly() {
+            for (pixel, &color) in self.buffer[span].iter_mut().zip(pixels) {
+                *pixel = color & 0xFFFFFF;
+            }
+        } else {
+            for (index, &color) in span.zip(pixels) {
+                self.blend_at(index, color | 0xFF000000);
+            }
+        }
+    }
+
+    /// Draws column `texture_x` of `texture` stretched over the `height`
+    /// pixels below `top` in screen column `x`, as walls are drawn. Texels
+    /// are treated as opaque.
+    pub fn texture_span(&mut self, x: usize, top: f32, height: f32, texture: &Texture, texture_x: usize) {
+        if height <= 0.0 {
+            return;
+        }
+        let first = top.max(0.0) as isize;
+        let last = ((top + height) as isize).max(first);
+        let area = self.visible(Rect::new(x as isize, first, 1, (last - first) as usize));
+        let origin_y = self.viewport().origin.1;
+
+        let column = texture_x.min(texture.width - 1);
+        for y in area.y..area.bottom() {
+            let local_y = (y - origin_y) as f32;
+            let texture_y = (((local_y - top) / height * texture.height as f32) as usize).min(texture.height - 1);
+            let color = texture.data[texture_y * texture.width + column];
+            let index = y as usize * self.stride + area.x as usize;
+            if self.writes_directly() {
+                self.buffer[index] = color & 0xFFFFFF;
+            } else {
+                self.blend_at(index, color | 0xFF000000);
+            }
         }
     }
-    
 
     pub fn set_background_color(&mut self, color: u32) {
         self.background_color = color;
@@ -56,38 +341,349 @@ impl Framebuffer {
         self.current_color = color;
     }
 
-    fn update_frame_time(&mut self) -> f32 {
-        let now = Instant::now();
-        let delta = now.duration_since(self.last_frame_time);
-        self.last_frame_time = now;
-        delta.as_secs_f32()
+    /// Draws the `src` part of `texture` stretched over `dst`, skipping
+    /// whatever falls outside the viewport. Reads past the texture's edges
+    /// repeat its border texels.
+    pub fn blit(&mut self, texture: &Texture, src: Rect, dst: Rect, options: &BlitOptions) {
+        if src.width == 0 || src.height == 0 || dst.width == 0 || dst.height == 0 {
+            return;
+        }
+
+        let scale_x = src.width as f32 / dst.width as f32;
+        let scale_y = src.height as f32 / dst.height as f32;
+        let area = self.visible(dst);
+        let origin = self.viewport().origin;
+        let (left, top) = (dst.x + origin.0, dst.y + origin.1);
+
+        let texel_x = |x: isize| {
+            let dx = (x - left) as usize;
+            let column = if options.flip_x { dst.width - 1 - dx } else { dx };
+            src.x as f32 + (column as f32 + 0.5) * scale_x
+        };
+        // Nearest filtering reads the same texture columns on every row.
+        let columns: Vec<usize> = match options.filter {
+            Filter::Nearest => (area.x..area.right())
+                .map(|x| (texel_x(x).max(0.0) as usize).min(texture.width - 1))
+                .collect(),
+            Filter::Bilinear => Vec::new(),
+        };
+
+        for y in area.y..area.bottom() {
+            let dy = (y - top) as usize;
+            let row = if options.flip_y { dst.height - 1 - dy } else { dy };
+            let v = src.y as f32 + (row as f32 + 0.5) * scale_y;
+            let line = y as usize * self.stride;
+
+            match options.filter {
+                Filter::Nearest => {
+                    let texels = &texture.data[(v.max(0.0) as usize).min(texture.height - 1) * texture.width..];
+                    for (x, &column) in (area.x..area.right()).zip(&columns) {
+                        let texel = texels[column];
+                        self.blend_at(line + x as usize, if options.alpha { texel } else { texel | 0xFF000000 });
+                    }
+                }
+                Filter::Bilinear => {
+                    for x in area.x..area.right() {
+                        let texel = texture.bilinear(texel_x(x) - 0.5, v - 0.5);
+                        self.blend_at(line + x as usize, if options.alpha { texel } else { texel | 0xFF000000 });
+                    }
+                }
+            }
+        }
     }
 
-    pub fn draw_fps(&mut self, x: usize, y: usize) {
-        let fps = 1.0 / self.update_frame_time();
+    /// Draws the frame rate with its right edge at `x`.
+    pub fn draw_fps(&mut self, x: usize, y: usize, delta: f32) {
+        if delta <= 0.0 {
+            return;
+        }
+        let fps = 1.0 / delta;
         let fps_text = format!("{:.2} FPS", fps);
-        self.draw_text(x as u32, y as u32, &fps_text);
+        let style = TextStyle {
+            align: Align::Right,
+            ..TextStyle::default()
+        };
+        self.draw_text(x, y, &fps_text, &style);
+    }
+
+    /// Width in pixels of the widest line of `text`.
+    pub fn text_width(text: &str, scale: usize) -> usize {
+        text.lines()
+            .map(|line| line.chars().count() * CHAR_ADVANCE)
+            .max()
+            .unwrap_or(0)
+            .saturating_sub(1)
+            * scale
+    }
+
+    /// Splits `text` into lines at its newlines and, given a width, wherever
+    /// a word would cross it. Words wider than a whole line are broken and
+    /// runs of spaces collapse to one.
+    pub fn wrap_text(text: &str, scale: usize, max_width: Option<usize>) -> Vec<String> {
+        let Some(max_width) = max_width else {
+            return text.lines().map(str::to_string).collect();
+        };
+        let max_chars = ((max_width / scale + 1) / CHAR_ADVANCE).max(1);
+
+        let mut lines = Vec::new();
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split(' ').filter(|word| !word.is_empty()) {
+                let mut word: Vec<char> = word.chars().collect();
+                let line_len = line.chars().count();
+                if line_len > 0 && line_len + 1 + word.len() > max_chars {
+                    lines.push(std::mem::take(&mut line));
+                } else if line_len > 0 {
+                    line.push(' ');
+                }
+                while word.len() > max_chars {
+                    lines.push(word.drain(..max_chars).collect());
+                }
+                line.extend(word);
+            }
+            lines.push(line);
+        }
+        lines
     }
-    fn draw_text(&mut self, x: u32, y: u32, text: &str) {
-        let mut offset_x = x;
-        for ch in text.chars() {
-            self.draw_char(offset_x, y, ch);
-            offset_x += 8; 
+
+    /// Draws `text` with its first line's top edge at `y`. `x` is the left
+    /// edge, center or right edge of each line depending on the alignment.
+    /// Returns the height of the drawn block.
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, style: &TextStyle) -> usize {
+        let scale = style.scale.max(1);
+        let lines = Self::wrap_text(text, scale, style.max_width);
+        self.set_current_color(style.color);
+
+        for (line_index, line) in lines.iter().enumerate() {
+            let width = Self::text_width(line, scale);
+            let left = match style.align {
+                Align::Left => x as isize,
+                Align::Center => x as isize - width as isize / 2,
+                Align::Right => x as isize - width as isize,
+            };
+            let top = (y + line_index * LINE_HEIGHT * scale) as isize;
+
+            for (char_index, c) in line.chars().enumerate() {
+                let char_left = left + (char_index * CHAR_ADVANCE * scale) as isize;
+                self.draw_glyph(char_left, top, c, scale);
+            }
         }
+
+        lines.len() * LINE_HEIGHT * scale
     }
 
-    fn draw_char(&mut self, x: u32, y: u32, char: char) {
-        if let Some(digit) = char.to_digit(10) {
-            let pattern = Self::DIGITS[digit as usize];
-            for (i, row) in pattern.iter().enumerate() {
-                for j in 0..5 {
-                    if (row >> (4 - j)) & 1 == 1 {
-                        self.point((x + j as u32) as usize, (y + i as u32) as usize);
+    fn draw_glyph(&mut self, x: isize, y: isize, c: char, scale: usize) {
+        for (row_index, row) in font::glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if (row >> (GLYPH_WIDTH - 1 - column)) & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let px = x + (column * scale + dx) as isize;
+                        let py = y + (row_index * scale + dy) as isize;
+                        if px >= 0 && py >= 0 {
+                            self.point(px as usize, py as usize);
+                        }
                     }
                 }
             }
         }
     }
-    
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width` by `height` opaque texture whose texels count up from 1, so
+    /// every texel can be told apart from the empty buffer.
+    fn numbered(width: usize, height: usize) -> Texture {
+        Texture {
+            data: (1..=(width * height) as u32).map(|n| 0xFF000000 | n).collect(),
+            width,
+            height,
+        }
+    }
+
+    /// What blitting a `numbered` texture 1:1 at `(dx, dy)` leaves at `(x, y)`.
+    fn expected(texture: &Texture, dx: isize, dy: isize, x: usize, y: usize, flip: (bool, bool)) -> u32 {
+        let (tx, ty) = (x as isize - dx, y as isize - dy);
+        if tx < 0 || ty < 0 || tx >= texture.width as isize || ty >= texture.height as isize {
+            return 0;
+        }
+        let tx = if flip.0 { texture.width as isize - 1 - tx } else { tx };
+        let ty = if flip.1 { texture.height as isize - 1 - ty } else { ty };
+        texture.data[ty as usize * texture.width + tx as usize] & 0xFFFFFF
+    }
+
+    fn blit_at(texture: &Texture, dx: isize, dy: isize, options: &BlitOptions) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(4, 3);
+        framebuffer.blit(texture, texture.bounds(), Rect::new(dx, dy, texture.width, texture.height), options);
+        framebuffer
+    }
+
+    fn assert_blit(texture: &Texture, dx: isize, dy: isize, options: &BlitOptions) {
+        let framebuffer = blit_at(texture, dx, dy, options);
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(
+                    framebuffer.buffer[y * 4 + x],
+                    expected(texture, dx, dy, x, y, (options.flip_x, options.flip_y)),
+                    "dst ({}, {}), pixel ({}, {})",
+                    dx,
+                    dy,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn blit_clips_at_every_edge() {
+        let texture = numbered(4, 3);
+        for (dx, dy) in [(0, 0), (-2, -1), (2, 0), (0, 2), (3, 2), (-3, -2), (-4, 0), (4, 3)] {
+            assert_blit(&texture, dx, dy, &BlitOptions::default());
+        }
+    }
+
+    #[test]
+    fn blit_clips_a_texture_larger_than_the_framebuffer() {
+        let texture = numbered(7, 5);
+        for (dx, dy) in [(0, 0), (-2, -1), (-3, -2), (1, 1)] {
+            assert_blit(&texture, dx, dy, &BlitOptions::default());
+        }
+    }
+
+    #[test]
+    fn flips_mirror_the_whole_rect_when_it_is_clipped() {
+        let texture = numbered(4, 3);
+        for (flip_x, flip_y) in [(true, false), (false, true), (true, true)] {
+            let options = BlitOptions { flip_x, flip_y, ..Default::default() };
+            for (dx, dy) in [(-1, 0), (0, -1), (2, 1), (-2, -2)] {
+                assert_blit(&texture, dx, dy, &options);
+            }
+        }
+    }
+
+    #[test]
+    fn alpha_option_decides_whether_transparent_texels_are_drawn() {
+        let texture = Texture { data: vec![0x00FF0000, 0xFF00FF00], width: 2, height: 1 };
+        let blended = blit_at(&texture, 0, 0, &BlitOptions::default());
+        let opaque = blit_at(&texture, 0, 0, &BlitOptions { alpha: false, ..Default::default() });
+
+        assert_eq!(&blended.buffer[..2], &[0x000000, 0x00FF00]);
+        assert_eq!(&opaque.buffer[..2], &[0xFF0000, 0x00FF00]);
+    }
+
+    #[test]
+    fn text_width_counts_characters_not_bytes() {
+        assert_eq!(Framebuffer::text_width("", 1), 0);
+        assert_eq!(Framebuffer::text_width("abc", 1), 17);
+        assert_eq!(Framebuffer::text_width("abc", 3), 51);
+        assert_eq!(Framebuffer::text_width("ñandú", 1), 29);
+        assert_eq!(Framebuffer::text_width("a\nmás ancha\nb", 2), 2 * (9 * 6 - 1));
+    }
+
+    #[test]
+    fn wrap_text_fits_words_in_the_width() {
+        let wrap = |text: &str, scale: usize, width: usize| Framebuffer::wrap_text(text, scale, Some(width));
+
+        assert_eq!(Framebuffer::wrap_text("uno  dos\ntres", 1, None), ["uno  dos", "tres"]);
+        // 17 pixels hold exactly three characters, 16 only two.
+        assert_eq!(wrap("abc abc", 1, 17), ["abc", "abc"]);
+        assert_eq!(wrap("abc", 1, 16), ["ab", "c"]);
+        assert_eq!(wrap("uno dos tres", 1, 41), ["uno dos", "tres"]);
+        assert_eq!(wrap("abcdefgh", 1, 17), ["abc", "def", "gh"]);
+        assert_eq!(wrap("a   b  c", 1, 100), ["a b c"]);
+        assert_eq!(wrap("a\n\nb", 1, 100), ["a", "", "b"]);
+        assert_eq!(wrap("árbol útil", 2, 70), ["árbol", "útil"]);
+        assert_eq!(wrap("ab", 3, 1), ["a", "b"]);
+
+        for line in wrap("el camión dejó caer cajas", 2, 70) {
+            assert!(Framebuffer::text_width(&line, 2) <= 70, "{:?}", line);
+        }
+    }
+
+    const SRC: u32 = 0xC86400;
+    const DST: u32 = 0x64C832;
+
+    #[test]
+    fn blend_modes_at_each_alpha() {
+        let table = [
+            (BlendMode::Normal, 0, DST),
+            (BlendMode::Normal, 128, 0x969518),
+            (BlendMode::Normal, 255, SRC),
+            (BlendMode::Additive, 0, DST),
+            (BlendMode::Additive, 128, 0xC8FA32),
+            (BlendMode::Additive, 255, 0xFFFF32),
+            (BlendMode::Multiply, 0, DST),
+            (BlendMode::Multiply, 128, 0x598A18),
+            (BlendMode::Multiply, 255, 0x4E4E00),
+        ];
+        for (mode, alpha, expected) in table {
+            assert_eq!(blend(SRC, DST, alpha, mode), expected, "{:?} con alpha {}", mode, alpha);
+        }
+    }
+
+    #[test]
+    fn opacity_scales_the_source_alpha() {
+        let draw = |mode: BlendMode, opacity: f32, color: u32| {
+            let mut framebuffer = Framebuffer::new(1, 1);
+            framebuffer.buffer[0] = DST;
+            framebuffer.set_blend_mode(mode);
+            framebuffer.set_opacity(opacity);
+            framebuffer.blend_point(0, 0, color);
+            framebuffer.buffer[0]
+        };
+
+        assert_eq!(draw(BlendMode::Normal, 1.0, 0xFF000000 | SRC), SRC);
+        assert_eq!(draw(BlendMode::Normal, 0.5, 0xFF000000 | SRC), 0x969518);
+        assert_eq!(draw(BlendMode::Normal, 1.0, 0x80000000 | SRC), 0x969518);
+        assert_eq!(draw(BlendMode::Additive, 0.5, 0xFF000000 | SRC), 0xC8FA32);
+        assert_eq!(draw(BlendMode::Multiply, 0.5, 0xFF000000 | SRC), 0x598A18);
+        assert_eq!(draw(BlendMode::Normal, 0.0, 0xFF000000 | SRC), DST);
+        assert_eq!(draw(BlendMode::Additive, 1.0, SRC), DST);
+    }
+
+    #[test]
+    fn nested_viewports_offset_and_clip() {
+        let mut framebuffer = Framebuffer::new(10, 8);
+        framebuffer.push_viewport(Rect::new(2, 1, 6, 5));
+        framebuffer.push_viewport(Rect::new(3, 2, 6, 6));
+        assert_eq!((framebuffer.width, framebuffer.height), (6, 6));
+
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.point(0, 0);
+        framebuffer.fill();
+
+        // Local (0, 0) is at (5, 3); the outer viewport cuts the fill at
+        // column 8 and row 6.
+        for y in 0..8 {
+            for x in 0..10 {
+                let drawn = (5..8).contains(&x) && (3..6).contains(&y);
+                assert_eq!(framebuffer.buffer[y * 10 + x] != 0, drawn, "pixel ({}, {})", x, y);
+            }
+        }
+
+        framebuffer.pop_viewport();
+        assert_eq!((framebuffer.width, framebuffer.height), (6, 5));
+        framebuffer.pop_viewport();
+        assert_eq!((framebuffer.width, framebuffer.height), (10, 8));
+        framebuffer.pop_viewport();
+        assert_eq!((framebuffer.width, framebuffer.height), (10, 8));
+    }
+
+    #[test]
+    fn viewport_left_of_its_parent_is_clipped() {
+        let mut framebuffer = Framebuffer::new(6, 4);
+        framebuffer.push_viewport(Rect::new(2, 0, 4, 4));
+        framebuffer.push_viewport(Rect::new(-3, 1, 4, 2));
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.fill();
+
+        let drawn: Vec<usize> = (0..24).filter(|&i| framebuffer.buffer[i] != 0).collect();
+        assert_eq!(drawn, [8, 14]);
+    }
+}
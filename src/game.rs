@@ -0,0 +1,279 @@
+use nalgebra_glm::Vec2;
+use rodio::{Decoder, OutputStreamHandle, Sink, Source};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::bindings::{Action, MouseSettings};
+use crate::framebuffer::{Align, Framebuffer, Rect, TextStyle};
+use crate::input::{InputRecorder, InputSource};
+use crate::maze::{load_maze, Level, Maze, MazeError};
+use crate::pathfinding::path_to_goal;
+use crate::player::Player;
+use crate::render::{render3d, render_2d, render_minimap, render_stamina, PANEL_COLOR, PANEL_OPACITY};
+use crate::scene::{Scene, Transition};
+use crate::simulation::Simulation;
+use crate::sprite::{render_sprites, SpriteTextures};
+use crate::texture::TextureRegistry;
+
+pub const SIMULATION_RATE: f32 = 60.0;
+const FADE_IN: f32 = 0.5;
+const MINIMAP_SIZE: (usize, usize) = (200, 150);
+
+pub fn spawn_player(level: &Level) -> Player {
+    Player {
+        pos: level.spawn,
+        a: level.facing,
+        fov: PI / 3.0,
+        radius: 10.0,
+        vel: Vec2::new(0.0, 0.0),
+        stamina: 1.0,
+        exhausted: false,
+        pitch: 0.0,
+        z: 0.0,
+        vz: 0.0,
+        crouch: 0.0,
+    }
+}
+
+const DEFAULT_MUSIC: &str = "./Assets/maintheme.wav";
+
+/// The sound at `path`, or `None` (logged) if it cannot be read.
+fn decode(path: &str) -> Option<Decoder<BufReader<File>>> {
+    let source = File::open(path)
+        .map_err(|error| error.to_string())
+        .and_then(|file| Decoder::new(BufReader::new(file)).map_err(|error| error.to_string()));
+    match source {
+        Ok(source) => Some(source),
+        Err(error) => {
+            eprintln!("No se pudo cargar el sonido {}: {}", path, error);
+            None
+        }
+    }
+}
+
+/// A paused sink playing `path`, or `None` if the track cannot be read.
+fn load_sink(stream: &OutputStreamHandle, path: &str) -> Option<Sink> {
+    let source = decode(path)?.amplify(0.1);
+    let sink = Sink::try_new(stream).unwrap();
+    sink.append(source);
+    sink.pause();
+    Some(sink)
+}
+
+/// One run through a level: the Playing scene. Music and the input
+/// recording belong to the run, so they stop when it is dropped.
+pub struct Game {
+    maze_file: String,
+    level: Level,
+    textures: TextureRegistry,
+    sprite_textures: SpriteTextures,
+    simulation: Simulation,
+    mouse: MouseSettings,
+    mode: &'static str,
+    fisheye: bool,
+    show_hint: bool,
+    playing_first: bool,
+    sink1: Sink,
+    sink2: Sink,
+    walking_sound_sink: Sink,
+    record_path: Option<String>,
+    recorder: Option<InputRecorder>,
+}
+
+impl Game {
+    pub fn new(
+        maze_file: &str,
+        stream: &OutputStreamHandle,
+        mouse: MouseSettings,
+        record_path: Option<String>,
+    ) -> Result<Game, MazeError> {
+        let level = load_maze(maze_file, 55)?;
+
+        let sink1 = level
+            .music
+            .as_deref()
+            .and_then(|music| load_sink(stream, music))
+            .or_else(|| load_sink(stream, DEFAULT_MUSIC))
+            .unwrap_or_else(|| Sink::try_new(stream).unwrap());
+        let sink2 = load_sink(stream, "./Assets/taylor.wav").unwrap_or_else(|| Sink::try_new(stream).unwrap());
+
+        let walking_sound_sink = Sink::try_new(stream).unwrap();
+        if let Some(source) = decode("./Assets/walking.wav") {
+            walking_sound_sink.append(source.amplify(0.4).repeat_infinite());
+        }
+        walking_sound_sink.pause();
+
+        sink1.play();
+
+        let mut game = Game {
+            maze_file: maze_file.to_string(),
+            textures: TextureRegistry::for_maze(maze_file),
+            sprite_textures: SpriteTextures::load(),
+            simulation: Simulation::new(&level),
+            level,
+            mouse,
+            mode: "2D",
+            fisheye: false,
+            show_hint: false,
+            playing_first: true,
+            sink1,
+            sink2,
+            walking_sound_sink,
+            record_path,
+            recorder: None,
+        };
+        game.restart();
+        Ok(game)
+    }
+
+    /// Puts the player back at the spawn with the clock at zero. A recording
+    /// starts over too, so it always holds the latest attempt.
+    pub fn restart(&mut self) {
+        self.simulation = Simulation::new(&self.level);
+        self.recorder = self.record_path.as_ref().map(|path| {
+            InputRecorder::create(path, &self.maze_file, &self.mouse).expect("No se pudo crear la grabación")
+        });
+        self.resume_music();
+    }
+
+    fn music(&self) -> &Sink {
+        if self.playing_first {
+            &self.sink1
+        } else {
+            &self.sink2
+        }
+    }
+
+    fn pause_audio(&self) {
+        self.walking_sound_sink.pause();
+        self.music().pause();
+    }
+
+    pub fn resume_music(&self) {
+        self.music().play();
+    }
+
+    pub fn title(&self) -> &str {
+        self.level.name.as_deref().unwrap_or("Proyecto 1")
+    }
+
+    pub fn update(&mut self, input: &dyn InputSource, dt: f32) -> Transition {
+        let maze = &self.level.maze;
+
+        if input.is_pressed(Action::Pause) || input.is_pressed(Action::Quit) {
+            self.pause_audio();
+            return Transition::Push(Scene::Paused);
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(input.frame()).expect("No se pudo escribir la grabación");
+        }
+        if self.simulation.frame(input, dt, maze, &self.mouse, Some(&self.walking_sound_sink)) {
+            self.pause_audio();
+            println!("Jugador alcanzó la posición 'F', mostrando imagen de victoria...");
+            let elapsed = self.simulation.time();
+            match self.level.par_time {
+                Some(par) => println!("Tiempo: {:.1} s (par: {:.1} s)", elapsed, par.as_secs_f32()),
+                None => println!("Tiempo: {:.1} s", elapsed),
+            }
+            return Transition::Push(Scene::Victory {
+                elapsed,
+                par: self.level.par_time.map(|par| par.as_secs_f32()),
+            });
+        }
+
+        if let Some(limit) = self.level.time_limit {
+            if self.simulation.time() >= limit.as_secs_f32() {
+                self.pause_audio();
+                println!("Se acabó el tiempo ({:.0} s)", limit.as_secs_f32());
+                return Transition::Push(Scene::Defeat { limit: limit.as_secs_f32() });
+            }
+        }
+
+        let pos = self.simulation.player.pos;
+        if maze.cell_at_world(pos) == Maze::OUTSIDE {
+            let (player_col, player_row) = maze.cell_coords(pos);
+            println!("Posición del jugador fuera de los límites: ({}, {})", player_row, player_col);
+        }
+
+        if input.is_pressed(Action::ToggleMap) {
+            self.mode = if self.mode == "2D" { "3D" } else { "2D" };
+        }
+
+        if input.is_pressed(Action::ToggleHint) {
+            self.show_hint = !self.show_hint;
+        }
+
+        if input.is_pressed(Action::ToggleFisheye) {
+            self.fisheye = !self.fisheye;
+        }
+
+        if input.is_pressed(Action::ToggleMusic) {
+            if self.playing_first {
+                self.sink1.pause();
+                self.sink2.play();
+            } else {
+                self.sink2.pause();
+                self.sink1.play();
+            }
+            self.playing_first = !self.playing_first;
+        }
+
+        Transition::Stay
+    }
+
+    pub fn render(&self, framebuffer: &mut Framebuffer) {
+        let maze = &self.level.maze;
+        let sprites = &self.level.sprites;
+        let view = self.simulation.view();
+
+        let hint = if self.show_hint { path_to_goal(maze, self.simulation.player.pos) } else { None };
+
+        if self.mode == "2D" {
+            render_2d(framebuffer, &view, maze, &self.textures, sprites, &self.sprite_textures, hint.as_deref());
+        } else {
+            let depth_buffer = render3d(framebuffer, &view, maze, &self.textures, hint.as_deref(), self.fisheye);
+            render_sprites(framebuffer, &view, sprites, &self.sprite_textures, &depth_buffer, maze.block_size(), self.fisheye);
+            let minimap_scale = 5;
+            render_minimap(
+                framebuffer,
+                &view,
+                maze,
+                &self.textures,
+                sprites,
+                &self.sprite_textures,
+                hint.as_deref(),
+                minimap_scale,
+                Rect::new(12, 12, MINIMAP_SIZE.0, MINIMAP_SIZE.1),
+            );
+        }
+
+        render_stamina(framebuffer, &view);
+
+        let time = self.simulation.time();
+        let clock = match self.level.time_limit {
+            Some(limit) => format!("{:.1} / {:.0} s", time, limit.as_secs_f32()),
+            None => format!("{:.1} s", time),
+        };
+        let style = TextStyle {
+            scale: 2,
+            align: Align::Center,
+            ..TextStyle::default()
+        };
+        let clock_width = Framebuffer::text_width(&clock, style.scale);
+        framebuffer.set_current_color(PANEL_COLOR);
+        framebuffer.set_opacity(PANEL_OPACITY);
+        framebuffer.fill_rect((framebuffer.width.saturating_sub(clock_width) / 2).saturating_sub(8), 4, clock_width + 16, 32);
+        framebuffer.set_opacity(1.0);
+        framebuffer.draw_text(framebuffer.width / 2, 10, &clock, &style);
+
+        // The level fades in from black.
+        if time < FADE_IN {
+            framebuffer.set_current_color(0x000000);
+            framebuffer.set_opacity(1.0 - time / FADE_IN);
+            framebuffer.fill();
+            framebuffer.set_opacity(1.0);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Backtracker,
+    Prim,
+    Kruskal,
+    Wilson,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 4] = [Algorithm::Backtracker, Algorithm::Prim, Algorithm::Kruskal, Algorithm::Wilson];
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Algorithm, String> {
+        match s {
+            "backtracker" => Ok(Algorithm::Backtracker),
+            "prim" => Ok(Algorithm::Prim),
+            "kruskal" => Ok(Algorithm::Kruskal),
+            "wilson" => Ok(Algorithm::Wilson),
+            _ => Err(format!("algoritmo desconocido `{}` (backtracker, prim, kruskal, wilson)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Algorithm::Backtracker => "backtracker",
+            Algorithm::Prim => "prim",
+            Algorithm::Kruskal => "kruskal",
+            Algorithm::Wilson => "wilson",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Size is in maze cells; every cell is two characters wide in the text
+/// format. `loop_density` is the chance of knocking down each wall left
+/// standing by the algorithm, so `0.0` keeps the maze perfect.
+pub struct GeneratorOptions {
+    pub algorithm: Algorithm,
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub loop_density: f32,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> GeneratorOptions {
+        GeneratorOptions {
+            algorithm: Algorithm::Backtracker,
+            width: 12,
+            height: 8,
+            seed: 0,
+            loop_density: 0.0,
+        }
+    }
+}
+
+// SplitMix64, so the same seed yields the same maze everywhere.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    fn chance(&mut self, p: f32) -> bool {
+        ((self.next_u64() >> 40) as f32 / (1u64 << 24) as f32) < p
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Walls of a `width` x `height` cell grid. `east[i]` is the wall on the
+/// right of cell `i`, `south[i]` the one below it.
+struct Grid {
+    width: usize,
+    height: usize,
+    east: Vec<bool>,
+    south: Vec<bool>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            width,
+            height,
+            east: vec![true; width * height],
+            south: vec![true; width * height],
+        }
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let (col, row) = (cell % self.width, cell / self.width);
+        let mut neighbors = Vec::with_capacity(4);
+        if row > 0 {
+            neighbors.push(cell - self.width);
+        }
+        if col + 1 < self.width {
+            neighbors.push(cell + 1);
+        }
+        if row + 1 < self.height {
+            neighbors.push(cell + self.width);
+        }
+        if col > 0 {
+            neighbors.push(cell - 1);
+        }
+        neighbors
+    }
+
+    fn wall(&mut self, a: usize, b: usize) -> &mut bool {
+        let (low, high) = (a.min(b), a.max(b));
+        if high == low + 1 {
+            &mut self.east[low]
+        } else {
+            &mut self.south[low]
+        }
+    }
+
+    fn carve(&mut self, a: usize, b: usize) {
+        *self.wall(a, b) = false;
+    }
+
+    fn is_open(&mut self, a: usize, b: usize) -> bool {
+        !*self.wall(a, b)
+    }
+
+    /// Every wall between two cells, as the pair of cells it separates.
+    fn interior_walls(&self) -> Vec<(usize, usize)> {
+        let mut walls = Vec::new();
+        for cell in 0..self.width * self.height {
+            if cell % self.width + 1 < self.width {
+                walls.push((cell, cell + 1));
+            }
+            if cell / self.width + 1 < self.height {
+                walls.push((cell, cell + self.width));
+            }
+        }
+        walls
+    }
+}
+
+fn backtracker(grid: &mut Grid, rng: &mut Rng) {
+    let mut visited = vec![false; grid.width * grid.height];
+    let mut stack = vec![0];
+    visited[0] = true;
+
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<usize> = grid.neighbors(cell).into_iter().filter(|&n| !visited[n]).collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let next = unvisited[rng.below(unvisited.len())];
+        grid.carve(cell, next);
+        visited[next] = true;
+        stack.push(next);
+    }
+}
+
+fn prim(grid: &mut Grid, rng: &mut Rng) {
+    let mut in_maze = vec![false; grid.width * grid.height];
+    let start = rng.below(in_maze.len());
+    in_maze[start] = true;
+    let mut frontier: Vec<(usize, usize)> = grid.neighbors(start).into_iter().map(|n| (start, n)).collect();
+
+    while !frontier.is_empty() {
+        let (from, to) = frontier.swap_remove(rng.below(frontier.len()));
+        if in_maze[to] {
+            continue;
+        }
+        grid.carve(from, to);
+        in_maze[to] = true;
+        for next in grid.neighbors(to) {
+            if !in_maze[next] {
+                frontier.push((to, next));
+            }
+        }
+    }
+}
+
+fn kruskal(grid: &mut Grid, rng: &mut Rng) {
+    fn find(parent: &mut [usize], mut cell: usize) -> usize {
+        while parent[cell] != cell {
+            parent[cell] = parent[parent[cell]];
+            cell = parent[cell];
+        }
+        cell
+    }
+
+    let mut parent: Vec<usize> = (0..grid.width * grid.height).collect();
+    let mut walls = grid.interior_walls();
+    rng.shuffle(&mut walls);
+
+    for (a, b) in walls {
+        let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+        if root_a != root_b {
+            parent[root_a] = root_b;
+            grid.carve(a, b);
+        }
+    }
+}
+
+fn wilson(grid: &mut Grid, rng: &mut Rng) {
+    let cells = grid.width * grid.height;
+    let mut in_maze = vec![false; cells];
+    in_maze[rng.below(cells)] = true;
+
+    // Direction the random walk last left each cell by.
+    let mut next_step = vec![0; cells];
+
+    for start in 0..cells {
+        if in_maze[start] {
+            continue;
+        }
+
+        let mut cell = start;
+        while !in_maze[cell] {
+            let neighbors = grid.neighbors(cell);
+            let next = neighbors[rng.below(neighbors.len())];
+            next_step[cell] = next;
+            cell = next;
+        }
+
+        // Following the last exit from each cell erases the walk's loops.
+        let mut cell = start;
+        while !in_maze[cell] {
+            in_maze[cell] = true;
+            grid.carve(cell, next_step[cell]);
+            cell = next_step[cell];
+        }
+    }
+}
+
+fn farthest_cell(grid: &mut Grid, from: usize) -> usize {
+    let mut distance = vec![usize::MAX; grid.width * grid.height];
+    let mut queue = VecDeque::from([from]);
+    distance[from] = 0;
+    let mut farthest = from;
+
+    while let Some(cell) = queue.pop_front() {
+        if distance[cell] > distance[farthest] {
+            farthest = cell;
+        }
+        for next in grid.neighbors(cell) {
+            if distance[next] == usize::MAX && grid.is_open(cell, next) {
+                distance[next] = distance[cell] + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    farthest
+}
+
+/// Generates a maze in the format read by `maze::load_maze`, with the spawn
+/// in the top-left cell and the goal in the cell farthest from it.
+pub fn generate(options: &GeneratorOptions) -> String {
+    // A single cell would have nowhere to put the goal.
+    let width = options.width.max(2);
+    let height = options.height.max(1);
+    let mut rng = Rng(options.seed);
+    let mut grid = Grid::new(width, height);
+
+    match options.algorithm {
+        Algorithm::Backtracker => backtracker(&mut grid, &mut rng),
+        Algorithm::Prim => prim(&mut grid, &mut rng),
+        Algorithm::Kruskal => kruskal(&mut grid, &mut rng),
+        Algorithm::Wilson => wilson(&mut grid, &mut rng),
+    }
+
+    for (a, b) in grid.interior_walls() {
+        if !grid.is_open(a, b) && rng.chance(options.loop_density) {
+            grid.carve(a, b);
+        }
+    }
+
+    let goal = farthest_cell(&mut grid, 0);
+
+    let mut text = format!(
+        "name = Laberinto aleatorio ({}, semilla {})\nfacing = south\n---\n",
+        options.algorithm, options.seed
+    );
+
+    text.push_str(&"+--".repeat(width));
+    text.push_str("+\n");
+    for row in 0..height {
+        text.push('|');
+        for col in 0..width {
+            let cell = row * width + col;
+            text.push_str(match cell {
+                0 => " @",
+                _ if cell == goal => " F",
+                _ => "  ",
+            });
+            text.push(if grid.east[cell] { '|' } else { ' ' });
+        }
+        text.push_str("\n+");
+        for col in 0..width {
+            text.push_str(if grid.south[row * width + col] { "--+" } else { "  +" });
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::parse_maze;
+
+    #[test]
+    fn every_algorithm_generates_a_loadable_maze() {
+        for algorithm in Algorithm::ALL {
+            for loop_density in [0.0, 0.3] {
+                let options = GeneratorOptions {
+                    algorithm,
+                    seed: 42,
+                    loop_density,
+                    ..Default::default()
+                };
+                let text = generate(&options);
+                assert_eq!(text, generate(&options), "{} no es determinista", algorithm);
+
+                if let Err(error) = parse_maze(&text, 55) {
+                    panic!("{} ({}): {}", algorithm, loop_density, error);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use minifb::{MouseButton, MouseMode as CursorMode, Window};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use crate::bindings::{Action, KeyBindings, MouseSettings};
+
+/// Everything the game reads from the player during one rendered frame,
+/// which lasted `dt` seconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    pub dt: f32,
+    pub down: Vec<Action>,
+    pub pressed: Vec<Action>,
+    pub mouse_delta: (f32, f32),
+    pub mouse_pos: Option<(f32, f32)>,
+    pub mouse_down: bool,
+    pub window_size: (usize, usize),
+}
+
+impl InputFrame {
+    /// One line of a recording, e.g.
+    /// `dt=0.016 down=move_forward pressed= delta=3,0 pos=450,300 click=0 size=900,600`.
+    pub fn to_line(&self) -> String {
+        let actions = |actions: &[Action]| actions.iter().map(|a| a.name()).collect::<Vec<_>>().join(",");
+        let pos = match self.mouse_pos {
+            Some((x, y)) => format!("{},{}", x, y),
+            None => "-".to_string(),
+        };
+        format!(
+            "dt={} down={} pressed={} delta={},{} pos={} click={} size={},{}",
+            self.dt,
+            actions(&self.down),
+            actions(&self.pressed),
+            self.mouse_delta.0,
+            self.mouse_delta.1,
+            pos,
+            self.mouse_down as u8,
+            self.window_size.0,
+            self.window_size.1,
+        )
+    }
+
+    pub fn parse(line: &str) -> Option<InputFrame> {
+        fn pair<T: std::str::FromStr>(value: &str) -> Option<(T, T)> {
+            let (a, b) = value.split_once(',')?;
+            Some((a.parse().ok()?, b.parse().ok()?))
+        }
+        fn actions(value: &str) -> Option<Vec<Action>> {
+            value.split(',').filter(|name| !name.is_empty()).map(Action::from_name).collect()
+        }
+
+        let mut frame = InputFrame::default();
+        for field in line.split_whitespace() {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "dt" => frame.dt = value.parse().ok()?,
+                "down" => frame.down = actions(value)?,
+                "pressed" => frame.pressed = actions(value)?,
+                "delta" => frame.mouse_delta = pair(value)?,
+                "pos" => frame.mouse_pos = if value == "-" { None } else { Some(pair(value)?) },
+                "click" => frame.mouse_down = value == "1",
+                "size" => frame.window_size = pair(value)?,
+                _ => return None,
+            }
+        }
+        Some(frame)
+    }
+}
+
+/// Source of player input, read one frame at a time. Implementations only
+/// decide where the current frame comes from.
+pub trait InputSource {
+    fn frame(&self) -> &InputFrame;
+
+    fn is_down(&self, action: Action) -> bool {
+        self.frame().down.contains(&action)
+    }
+
+    fn is_pressed(&self, action: Action) -> bool {
+        self.frame().pressed.contains(&action)
+    }
+
+    fn mouse_delta(&self) -> (f32, f32) {
+        self.frame().mouse_delta
+    }
+
+    fn mouse_pos(&self) -> Option<(f32, f32)> {
+        self.frame().mouse_pos
+    }
+
+    fn mouse_down(&self) -> bool {
+        self.frame().mouse_down
+    }
+
+    fn window_size(&self) -> (usize, usize) {
+        self.frame().window_size
+    }
+}
+
+/// Live input from a minifb window, translated through the key bindings.
+pub struct MinifbInput {
+    bindings: KeyBindings,
+    frame: InputFrame,
+    last_mouse: Option<(f32, f32)>,
+}
+
+impl MinifbInput {
+    pub fn new(bindings: KeyBindings) -> MinifbInput {
+        MinifbInput {
+            bindings,
+            frame: InputFrame::default(),
+            last_mouse: None,
+        }
+    }
+
+    pub fn mouse_settings(&self) -> &MouseSettings {
+        &self.bindings.mouse
+    }
+
+    /// Takes a snapshot of the window's input.
+    pub fn update(&mut self, window: &Window, dt: f32) {
+        let mouse_pos = window.get_mouse_pos(CursorMode::Pass);
+        let mouse_delta = match (self.last_mouse, mouse_pos) {
+            (Some(last), Some(pos)) => (pos.0 - last.0, pos.1 - last.1),
+            _ => (0.0, 0.0),
+        };
+        self.last_mouse = mouse_pos;
+
+        self.frame = InputFrame {
+            dt,
+            down: Action::ALL.iter().copied().filter(|&a| self.bindings.is_down(window, a)).collect(),
+            pressed: Action::ALL.iter().copied().filter(|&a| self.bindings.is_pressed(window, a)).collect(),
+            mouse_delta,
+            mouse_pos: window.get_mouse_pos(CursorMode::Discard),
+            mouse_down: window.get_mouse_down(MouseButton::Left),
+            window_size: window.get_size(),
+        };
+    }
+}
+
+impl InputSource for MinifbInput {
+    fn frame(&self) -> &InputFrame {
+        &self.frame
+    }
+}
+
+/// Plays back a fixed list of frames, for tests and bots.
+pub struct ScriptedInput {
+    frames: Vec<InputFrame>,
+    next: usize,
+    current: InputFrame,
+}
+
+impl ScriptedInput {
+    pub fn new(frames: Vec<InputFrame>) -> ScriptedInput {
+        ScriptedInput {
+            frames,
+            next: 0,
+            current: InputFrame::default(),
+        }
+    }
+
+    /// Moves to the next frame. Returns `false` once the script has run out,
+    /// after which the input stays idle.
+    pub fn advance(&mut self) -> bool {
+        match self.frames.get(self.next) {
+            Some(frame) => {
+                self.current = frame.clone();
+                self.next += 1;
+                true
+            }
+            None => {
+                self.current = InputFrame::default();
+                false
+            }
+        }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn frame(&self) -> &InputFrame {
+        &self.current
+    }
+}
+
+/// A recording written by `InputRecorder`: a `maze = <file>` line, the mouse
+/// settings it was made with, a `maze_lines = <n>` line followed by a copy
+/// of the maze, then one `InputFrame` line per frame. The copy keeps a
+/// recording playable after its maze file changes, as the random maze does
+/// every time one is generated.
+pub struct RecordedInput {
+    script: ScriptedInput,
+    pub maze_file: String,
+    pub maze: String,
+    pub mouse: MouseSettings,
+}
+
+impl RecordedInput {
+    pub fn load(path: &str) -> io::Result<RecordedInput> {
+        let invalid = |line_number: usize| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: línea inválida", path, line_number + 1))
+        };
+
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().enumerate();
+
+        let (_, maze_line) = lines.next().ok_or_else(|| invalid(0))?;
+        let maze_file = maze_line.strip_prefix("maze = ").ok_or_else(|| invalid(0))?.to_string();
+
+        let (_, mouse_line) = lines.next().ok_or_else(|| invalid(1))?;
+        let mouse = MouseSettings::parse(mouse_line.strip_prefix("mouse = ").unwrap_or_default()).ok_or_else(|| invalid(1))?;
+
+        let (_, count_line) = lines.next().ok_or_else(|| invalid(2))?;
+        let count: usize = count_line
+            .strip_prefix("maze_lines = ")
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| invalid(2))?;
+        let mut maze = String::new();
+        for index in 0..count {
+            let (_, line) = lines.next().ok_or_else(|| invalid(3 + index))?;
+            maze.push_str(line);
+            maze.push('\n');
+        }
+
+        let frames = lines
+            .map(|(line_number, line)| InputFrame::parse(line).ok_or_else(|| invalid(line_number)))
+            .collect::<io::Result<Vec<InputFrame>>>()?;
+
+        Ok(RecordedInput {
+            script: ScriptedInput::new(frames),
+            maze_file,
+            maze,
+            mouse,
+        })
+    }
+
+    pub fn advance(&mut self) -> bool {
+        self.script.advance()
+    }
+}
+
+impl InputSource for RecordedInput {
+    fn frame(&self) -> &InputFrame {
+        self.script.frame()
+    }
+}
+
+pub struct InputRecorder {
+    file: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn create(path: &str, maze_file: &str, mouse: &MouseSettings) -> io::Result<InputRecorder> {
+        let maze = fs::read_to_string(maze_file)?;
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "maze = {}", maze_file)?;
+        writeln!(file, "mouse = {}", mouse.to_line())?;
+        writeln!(file, "maze_lines = {}", maze.lines().count())?;
+        for line in maze.lines() {
+            writeln!(file, "{}", line)?;
+        }
+        Ok(InputRecorder { file })
+    }
+
+    pub fn record(&mut self, frame: &InputFrame) -> io::Result<()> {
+        writeln!(self.file, "{}", frame.to_line())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::MouseMode;
+
+    #[test]
+    fn frame_survives_a_round_trip_through_a_line() {
+        let frames = [
+            InputFrame::default(),
+            InputFrame {
+                dt: 0.016,
+                down: vec![Action::MoveForward, Action::Sprint],
+                pressed: vec![Action::Jump],
+                mouse_delta: (3.5, -2.0),
+                mouse_pos: Some((450.0, 300.25)),
+                mouse_down: true,
+                window_size: (900, 600),
+            },
+        ];
+        for frame in frames {
+            assert_eq!(InputFrame::parse(&frame.to_line()), Some(frame));
+        }
+    }
+
+    #[test]
+    fn recording_keeps_a_copy_of_its_maze() {
+        let dir = std::env::temp_dir().join(format!("grabacion_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let maze_file = dir.join("grabacion_laberinto.txt");
+        let recording = dir.join("grabacion.txt");
+        let maze = "name = Prueba\n---\n+-+-+\n|@ F|\n+-+-+\n";
+        fs::write(&maze_file, maze).unwrap();
+
+        let mouse = MouseSettings { mode: MouseMode::Edge, sensitivity: 0.01, invert: true };
+        let frame = InputFrame { dt: 0.02, down: vec![Action::TurnLeft], ..InputFrame::default() };
+        let mut recorder = InputRecorder::create(recording.to_str().unwrap(), maze_file.to_str().unwrap(), &mouse).unwrap();
+        recorder.record(&frame).unwrap();
+        recorder.record(&InputFrame::default()).unwrap();
+        drop(recorder);
+
+        // The recording must not depend on the maze file any more.
+        fs::write(&maze_file, "").unwrap();
+
+        let mut input = RecordedInput::load(recording.to_str().unwrap()).unwrap();
+        assert_eq!(input.maze, maze);
+        assert_eq!(input.mouse, mouse);
+        assert!(input.advance());
+        assert_eq!(input.frame(), &frame);
+        assert!(input.advance());
+        assert!(!input.advance());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
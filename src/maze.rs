@@ -1,14 +1,413 @@
+use nalgebra_glm::Vec2;
+use std::error::Error;
+use std::fmt;
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use crate::pathfinding::bfs;
+use crate::sprite::{extract_sprites, Sprite};
 
-pub fn load_maze(filename: &str) -> Vec<Vec<char>> {
-    let file = File::open(filename).unwrap();
+pub struct Maze {
+    grid: Vec<Vec<char>>,
+    width: usize,
+    height: usize,
+    block_size: usize,
+}
 
-    let reader = BufReader::new(file);
+impl Maze {
+    /// Returned for any lookup outside the grid, and used to pad ragged rows.
+    /// It is solid, so nothing can walk or see past the edge of the maze.
+    pub const OUTSIDE: char = '\0';
 
-    reader
-        .lines()
-        .map(|line| line.unwrap().chars().collect())
-        .collect()
-}
\ No newline at end of file
+    pub fn new(mut grid: Vec<Vec<char>>, block_size: usize) -> Maze {
+        let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let height = grid.len();
+        for row in grid.iter_mut() {
+            row.resize(width, Maze::OUTSIDE);
+        }
+
+        Maze {
+            grid,
+            width,
+            height,
+            block_size,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn contains(&self, col: isize, row: isize) -> bool {
+        col >= 0 && row >= 0 && (col as usize) < self.width && (row as usize) < self.height
+    }
+
+    pub fn cell(&self, col: isize, row: isize) -> char {
+        if self.contains(col, row) {
+            self.grid[row as usize][col as usize]
+        } else {
+            Maze::OUTSIDE
+        }
+    }
+
+    pub fn set_cell(&mut self, col: usize, row: usize, cell: char) {
+        if col < self.width && row < self.height {
+            self.grid[row][col] = cell;
+        }
+    }
+
+    pub fn cell_coords(&self, pos: Vec2) -> (isize, isize) {
+        let block = self.block_size as f32;
+        ((pos.x / block).floor() as isize, (pos.y / block).floor() as isize)
+    }
+
+    pub fn cell_center(&self, col: usize, row: usize) -> Vec2 {
+        let block = self.block_size as f32;
+        Vec2::new((col as f32 + 0.5) * block, (row as f32 + 0.5) * block)
+    }
+
+    pub fn cell_at_world(&self, pos: Vec2) -> char {
+        let (col, row) = self.cell_coords(pos);
+        self.cell(col, row)
+    }
+
+    pub fn is_solid(&self, col: isize, row: isize) -> bool {
+        !is_open(self.cell(col, row))
+    }
+
+    pub fn is_goal(&self, col: isize, row: isize) -> bool {
+        self.cell(col, row) == 'F'
+    }
+
+    pub fn goal(&self) -> Option<(usize, usize)> {
+        self.cells().find(|&(_, _, cell)| cell == 'F').map(|(col, row, _)| (col, row))
+    }
+
+    /// Open cells sharing an edge with `(col, row)`.
+    pub fn neighbors(&self, col: usize, row: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        [(0, -1), (1, 0), (0, 1), (-1, 0)]
+            .into_iter()
+            .map(move |(dc, dr)| (col as isize + dc, row as isize + dr))
+            .filter(move |&(c, r)| !self.is_solid(c, r))
+            .map(|(c, r)| (c as usize, r as usize))
+    }
+
+    /// Every cell in row-major order as `(col, row, cell)`.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        self.grid.iter().enumerate().flat_map(|(row_index, row)| {
+            row.iter().enumerate().map(move |(col_index, &cell)| (col_index, row_index, cell))
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum MazeError {
+    Io { path: String, source: io::Error },
+    UnknownCharacter { line: usize, column: usize, found: char },
+    MissingGoal,
+    DuplicateGoal { line: usize, column: usize },
+    OpenBorder { line: usize, column: usize },
+    MissingStart,
+    DuplicateStart { line: usize, column: usize },
+    InvalidHeader { line: usize, text: String },
+    UnreachableGoal { line: usize, column: usize },
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeError::Io { path, source } => write!(f, "no se pudo leer {}: {}", path, source),
+            MazeError::UnknownCharacter { line, column, found } => {
+                write!(f, "{}:{}: carácter desconocido {:?}", line, column, found)
+            }
+            MazeError::MissingGoal => write!(f, "el laberinto no tiene meta 'F'"),
+            MazeError::DuplicateGoal { line, column } => write!(f, "{}:{}: meta 'F' repetida", line, column),
+            MazeError::OpenBorder { line, column } => {
+                write!(f, "{}:{}: celda abierta en el borde del laberinto", line, column)
+            }
+            MazeError::MissingStart => write!(f, "el laberinto no marca dónde aparece el jugador"),
+            MazeError::DuplicateStart { line, column } => {
+                write!(f, "{}:{}: inicio del jugador repetido", line, column)
+            }
+            MazeError::InvalidHeader { line, text } => write!(f, "{}: encabezado inválido {:?}", line, text),
+            MazeError::UnreachableGoal { line, column } => {
+                write!(f, "{}:{}: la meta no se puede alcanzar desde el inicio", line, column)
+            }
+        }
+    }
+}
+
+impl Error for MazeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MazeError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn is_known(cell: char) -> bool {
+    matches!(cell, ' ' | '+' | '-' | '|') || cell.is_ascii_alphabetic()
+}
+
+/// A validated maze together with everything its file declares besides the
+/// grid: sprites, the player spawn and the optional header.
+pub struct Level {
+    pub maze: Maze,
+    pub sprites: Vec<Sprite>,
+    pub spawn: Vec2,
+    pub facing: f32,
+    pub name: Option<String>,
+    pub par_time: Option<Duration>,
+    pub time_limit: Option<Duration>,
+    pub music: Option<String>,
+}
+
+/// Marks the spawn in the grid. Letters are taken by walls and floors, so
+/// the marker is not one.
+const SPAWN: char = '@';
+
+fn facing_angle(direction: &str) -> Option<f32> {
+    match direction {
+        "east" => Some(0.0),
+        "south" => Some(PI / 2.0),
+        "west" => Some(PI),
+        "north" => Some(-PI / 2.0),
+        _ => None,
+    }
+}
+
+/// Loads and validates a maze file. The file may open with a header of
+/// `key = value` lines (`name`, `par` and `limit` in seconds, `music`, and
+/// `facing` as `north`, `east`, `south` or `west`) closed by `---`.
+/// In the grid, `@` marks the spawn, facing east unless the header says
+/// otherwise. `music` is relative to the maze file.
+/// Lines and columns in errors are 1-based and count the header.
+pub fn load_maze(filename: &str, block_size: usize) -> Result<Level, MazeError> {
+    let text = fs::read_to_string(filename).map_err(|source| MazeError::Io { path: filename.to_string(), source })?;
+    let mut level = parse_maze(&text, block_size)?;
+
+    if let Some(music) = level.music.as_mut() {
+        let base = Path::new(filename).parent().unwrap_or(Path::new("."));
+        let path: PathBuf = base.join(&*music).components().collect();
+        *music = path.to_string_lossy().into_owned();
+    }
+    Ok(level)
+}
+
+/// Same as `load_maze`, for a maze file's contents.
+pub fn parse_maze(text: &str, block_size: usize) -> Result<Level, MazeError> {
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+
+    let header_len = lines.iter().position(|line| line.trim() == "---").map_or(0, |i| i + 1);
+
+    let mut name = None;
+    let mut par_time = None;
+    let mut time_limit = None;
+    let mut music = None;
+    let mut facing = 0.0;
+    for (index, line) in lines[..header_len.saturating_sub(1)].iter().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let invalid = || MazeError::InvalidHeader { line: line_number, text: line.clone() };
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim().to_string();
+        let seconds = |value: &str| {
+            let seconds: f32 = value.parse().map_err(|_| invalid())?;
+            Duration::try_from_secs_f32(seconds).map_err(|_| invalid())
+        };
+        match key.trim() {
+            "name" => name = Some(value),
+            "par" => par_time = Some(seconds(&value)?),
+            "limit" => time_limit = Some(seconds(&value)?),
+            "music" => music = Some(value),
+            "facing" => facing = facing_angle(&value).ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        }
+    }
+
+    let grid = lines[header_len..]
+        .iter()
+        .map(|line| line.chars().collect())
+        .collect();
+
+    let mut maze = Maze::new(grid, block_size);
+    let sprites = extract_sprites(&mut maze);
+
+    let position = |col: usize, row: usize| (row + header_len + 1, col + 1);
+
+    let mut goal = None;
+    let mut start = None;
+    for (col, row, cell) in maze.cells() {
+        let (line, column) = position(col, row);
+        if cell == SPAWN {
+            if start.is_some() {
+                return Err(MazeError::DuplicateStart { line, column });
+            }
+            start = Some((col, row));
+            continue;
+        }
+        if cell != Maze::OUTSIDE && !is_known(cell) {
+            return Err(MazeError::UnknownCharacter { line, column, found: cell });
+        }
+        if cell == 'F' {
+            if goal.is_some() {
+                return Err(MazeError::DuplicateGoal { line, column });
+            }
+            goal = Some((col, row));
+        }
+    }
+    let goal = goal.ok_or(MazeError::MissingGoal)?;
+    let (start_col, start_row) = start.ok_or(MazeError::MissingStart)?;
+    maze.set_cell(start_col, start_row, ' ');
+
+    for (col, row, _) in maze.cells() {
+        let (c, r) = (col as isize, row as isize);
+        if maze.is_solid(c, r) {
+            continue;
+        }
+        let touches_outside = [(0, -1), (1, 0), (0, 1), (-1, 0)]
+            .iter()
+            .any(|(dc, dr)| maze.cell(c + dc, r + dr) == Maze::OUTSIDE);
+        if touches_outside {
+            let (line, column) = position(col, row);
+            return Err(MazeError::OpenBorder { line, column });
+        }
+    }
+
+    if bfs(&maze, (start_col, start_row), goal).is_none() {
+        let (line, column) = position(goal.0, goal.1);
+        return Err(MazeError::UnreachableGoal { line, column });
+    }
+
+    Ok(Level {
+        spawn: maze.cell_center(start_col, start_row),
+        maze,
+        sprites,
+        facing,
+        name,
+        par_time,
+        time_limit,
+        music,
+    })
+}
+
+// Lowercase letters are open cells that carry their own floor and ceiling.
+pub fn is_open(cell: char) -> bool {
+    cell == ' ' || cell == 'F' || cell.is_ascii_lowercase()
+}
+
+/// Open cells with a floor that changes how the player moves.
+const ICE: char = 'i';
+const MUD: char = 'm';
+
+/// Friction of each floor relative to plain ground.
+const ICE_FRICTION: f32 = 0.15;
+const MUD_FRICTION: f32 = 2.5;
+
+/// How strongly the floor of an open cell stops the player, relative to
+/// plain ground.
+pub fn friction(cell: char) -> f32 {
+    match cell {
+        ICE => ICE_FRICTION,
+        MUD => MUD_FRICTION,
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `grid` under a two-line header, so the grid starts on line 3.
+    fn error(grid: &str) -> MazeError {
+        match parse_maze(&format!("name = Prueba\n---\n{}", grid), 10) {
+            Ok(_) => panic!("se esperaba un error"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn unknown_character_counts_the_header() {
+        assert!(matches!(
+            error("+---+\n|@?F|\n+---+"),
+            MazeError::UnknownCharacter { line: 4, column: 3, found: '?' }
+        ));
+        assert!(matches!(
+            parse_maze("+---+\n|@?F|\n+---+", 10),
+            Err(MazeError::UnknownCharacter { line: 2, column: 3, found: '?' })
+        ));
+    }
+
+    #[test]
+    fn goal_must_appear_once() {
+        assert!(matches!(error("+----+\n|@FF |\n+----+"), MazeError::DuplicateGoal { line: 4, column: 4 }));
+        assert!(matches!(error("+---+\n|@  |\n+---+"), MazeError::MissingGoal));
+    }
+
+    #[test]
+    fn start_must_appear_once() {
+        assert!(matches!(error("+---+\n|@@F|\n+---+"), MazeError::DuplicateStart { line: 4, column: 3 }));
+        assert!(matches!(error("+---+\n|  F|\n+---+"), MazeError::MissingStart));
+    }
+
+    #[test]
+    fn open_border_reports_the_open_cell() {
+        assert!(matches!(error("+- -+\n|@ F|\n+---+"), MazeError::OpenBorder { line: 3, column: 3 }));
+        assert!(matches!(error("+---+\n|@ F \n+---+"), MazeError::OpenBorder { line: 4, column: 5 }));
+    }
+
+    #[test]
+    fn unreachable_goal_reports_the_goal() {
+        assert!(matches!(error("+---+\n|@|F|\n+---+"), MazeError::UnreachableGoal { line: 4, column: 4 }));
+    }
+
+    #[test]
+    fn invalid_header_reports_its_line() {
+        let text = "name = Prueba\nspeed = 3\n---\n+---+\n|@ F|\n+---+";
+        match parse_maze(text, 10) {
+            Err(MazeError::InvalidHeader { line, text }) => assert_eq!((line, text.as_str()), (2, "speed = 3")),
+            _ => panic!("se esperaba un encabezado inválido"),
+        }
+        assert!(matches!(
+            parse_maze("facing = up\n---\n+---+\n|@ F|\n+---+", 10),
+            Err(MazeError::InvalidHeader { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn spawn_marker_leaves_letters_to_the_map() {
+        let text = "facing = south\n---\n+---+\n|@ v|\n|S F|\n+---+";
+        let level = parse_maze(text, 10).unwrap();
+        assert_eq!(level.spawn, level.maze.cell_center(1, 1));
+        assert_eq!(level.facing, std::f32::consts::FRAC_PI_2);
+        assert_eq!(level.maze.cell(3, 1), 'v');
+        assert_eq!(level.maze.cell(1, 2), 'S');
+    }
+
+    #[test]
+    fn music_is_relative_to_the_maze_file() {
+        let dir = std::env::temp_dir().join(format!("maze_music_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("level.txt");
+        fs::write(&file, "music = ./Assets/song.wav\n---\n+--+\n|@F|\n+--+").unwrap();
+        let level = load_maze(file.to_str().unwrap(), 10).unwrap();
+        let expected = dir.join("Assets").join("song.wav");
+        assert_eq!(level.music.as_deref(), expected.to_str());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
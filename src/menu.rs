@@ -1,105 +1,318 @@
-use minifb::{Window, WindowOptions, Key, MouseMode, MouseButton};
-use image::GenericImageView;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub struct Menu {
-    window: Window,
-    easy_selected: bool,
-    hard_selected: bool,
-    background_image: Vec<u32>,
-    easy_highlight_image: Vec<u32>,
-    hard_highlight_image: Vec<u32>,
-    width: usize,
-    height: usize,
+use crate::bindings::Action;
+use crate::framebuffer::{Align, BlendMode, BlitOptions, Filter, Framebuffer, Rect, TextStyle};
+use crate::input::InputSource;
+use crate::texture::Texture;
+
+const OUTLINE_COLOR: u32 = 0xFFD700;
+const LABEL_COLOR: u32 = 0xFFFFFF;
+const GLOW_COLOR: u32 = 0x303020;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuAction {
+    Play(String),
+    Random,
+    Quit,
 }
 
-impl Menu {
-    pub fn new(window_width: usize, window_height: usize) -> Menu {
-        
-        let (background_image, width, height) = load_texture("./Assets/Menus/Menu(basic).png");
-        let (easy_highlight_image, _, _) = load_texture("./Assets/Menus/Menu(easy).png");
-        let (hard_highlight_image, _, _) = load_texture("./Assets/Menus/Menu(hard).png");
-
-        let window = Window::new(
-            "Menu",
-            window_width,
-            window_height,
-            WindowOptions::default(),
-        ).unwrap();
-
-        Menu {
-            window,
-            easy_selected: false,
-            hard_selected: false,
-            background_image,
-            easy_highlight_image,
-            hard_highlight_image,
-            width,
-            height,
+impl MenuAction {
+    fn parse(value: &str, base: &Path) -> Option<MenuAction> {
+        match value.split_once(' ') {
+            Some(("play", path)) => Some(MenuAction::Play(resolve(base, path.trim()))),
+            None if value == "random" => Some(MenuAction::Random),
+            None if value == "quit" => Some(MenuAction::Quit),
+            _ => None,
         }
     }
+}
+
+/// `bounds` is `(x, y, width, height)` in pixels of the menu's background.
+/// Without a highlight image, a selected button gets an outline instead.
+struct Button {
+    name: String,
+    label: String,
+    bounds: (usize, usize, usize, usize),
+    action: Option<MenuAction>,
+    highlight: Option<Texture>,
+}
+
+impl Button {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let (bx, by, width, height) = self.bounds;
+        x >= bx as f32 && y >= by as f32 && x < (bx + width) as f32 && y < (by + height) as f32
+    }
+}
+
+fn resolve(base: &Path, path: &str) -> String {
+    let path: PathBuf = base.join(path).components().collect();
+    path.to_string_lossy().into_owned()
+}
+
+/// A menu read from a `.menu` file: a background image and a list of
+/// buttons, kept in the order the file first mentions them. Buttons can be
+/// picked with the mouse or with the arrow keys and Enter.
+///
+/// ```text
+/// background = ./Assets/Menus/Menu(basic).png
+/// easy.label = Fácil
+/// easy.bounds = 180, 200, 180, 45
+/// easy.highlight = ./Assets/Menus/Menu(easy).png
+/// easy.action = play ./maze_easy.txt
+/// ```
+pub struct Menu {
+    background: Texture,
+    buttons: Vec<Button>,
+    selected: Option<usize>,
+}
+
+impl Menu {
+    pub fn load(path: &str) -> Result<Menu, String> {
+        let config = fs::read_to_string(path).map_err(|e| format!("no se pudo leer {}: {}", path, e))?;
+        let base = Path::new(path).parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let mut background = None;
+        let mut buttons: Vec<Button> = Vec::new();
 
-    pub fn run(&mut self) -> Option<&str> {
-        while self.window.is_open() && !self.window.is_key_down(Key::Escape) {
-            let mouse_pos = self.window.get_mouse_pos(MouseMode::Clamp).unwrap_or((0.0, 0.0));
-            let (mouse_x, mouse_y) = (mouse_pos.0 as usize, mouse_pos.1 as usize);
-    
-            self.easy_selected = self.is_mouse_over_easy(mouse_x, mouse_y);
-            self.hard_selected = self.is_mouse_over_hard(mouse_x, mouse_y);
-    
-            
-            let buffer = if self.easy_selected {
-                &self.easy_highlight_image
-            } else if self.hard_selected {
-                &self.hard_highlight_image
-            } else {
-                &self.background_image
+        for (line_number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                println!("{}:{}: se esperaba `clave = valor`", path, line_number + 1);
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || println!("{}:{}: valor inválido `{}`", path, line_number + 1, value);
+
+            if key == "background" {
+                let texture = Texture::load(&resolve(&base, value))
+                    .map_err(|e| format!("{}:{}: no se pudo cargar `{}`: {}", path, line_number + 1, value, e))?;
+                background = Some(texture);
+                continue;
+            }
+
+            let Some((name, field)) = key.split_once('.') else {
+                println!("{}:{}: clave desconocida `{}`", path, line_number + 1, key);
+                continue;
+            };
+
+            let index = match buttons.iter().position(|button| button.name == name) {
+                Some(index) => index,
+                None => {
+                    buttons.push(Button {
+                        name: name.to_string(),
+                        label: name.to_string(),
+                        bounds: (0, 0, 0, 0),
+                        action: None,
+                        highlight: None,
+                    });
+                    buttons.len() - 1
+                }
             };
-    
-            self.window.update_with_buffer(buffer, self.width, self.height).unwrap();
-    
-            if self.window.get_mouse_down(MouseButton::Left) {
-                if self.easy_selected {
-                    break;  
-                } else if self.hard_selected {
-                    break;  
+            let button = &mut buttons[index];
+
+            match field {
+                "label" => button.label = value.to_string(),
+                "bounds" => {
+                    let numbers: Result<Vec<usize>, _> = value.split(',').map(|n| n.trim().parse()).collect();
+                    match numbers.as_deref() {
+                        Ok(&[x, y, width, height]) => button.bounds = (x, y, width, height),
+                        _ => invalid(),
+                    }
                 }
+                "highlight" => match Texture::load(&resolve(&base, value)) {
+                    Ok(texture) => button.highlight = Some(texture),
+                    Err(e) => println!("{}:{}: no se pudo cargar `{}`: {}", path, line_number + 1, value, e),
+                },
+                "action" => match MenuAction::parse(value, &base) {
+                    Some(action) => button.action = Some(action),
+                    None => invalid(),
+                },
+                _ => println!("{}:{}: clave desconocida `{}`", path, line_number + 1, key),
+            }
+        }
+
+        for button in buttons.iter().filter(|button| button.action.is_none()) {
+            println!("{}: el botón `{}` no tiene acción", path, button.name);
+        }
+
+        Ok(Menu {
+            background: background.ok_or_else(|| format!("{}: falta `background`", path))?,
+            buttons,
+            selected: None,
+        })
+    }
+
+    /// Reacts to one frame of input, returning the chosen action once the
+    /// player picks a button.
+    pub fn update(&mut self, input: &dyn InputSource) -> Option<MenuAction> {
+        let count = self.buttons.len();
+        if count == 0 {
+            return None;
+        }
+
+        // Window pixels to background pixels, since the background is
+        // stretched over the whole window.
+        let (window_width, window_height) = input.window_size();
+        let hovered = input.mouse_pos().and_then(|(x, y)| {
+            let x = x * self.background.width as f32 / window_width.max(1) as f32;
+            let y = y * self.background.height as f32 / window_height.max(1) as f32;
+            self.buttons.iter().position(|button| button.contains(x, y))
+        });
+
+        // A mouse resting still does not take the selection away from the
+        // keyboard.
+        if input.mouse_delta() != (0.0, 0.0) {
+            self.selected = hovered;
+        }
+
+        if input.is_pressed(Action::MenuNext) {
+            self.selected = Some(self.selected.map_or(0, |i| (i + 1) % count));
+        }
+        if input.is_pressed(Action::MenuPrevious) {
+            self.selected = Some(self.selected.map_or(count - 1, |i| (i + count - 1) % count));
+        }
+
+        if input.is_pressed(Action::RandomLevel) {
+            if let Some(index) = self.buttons.iter().position(|b| b.action == Some(MenuAction::Random)) {
+                return self.activate(index);
+            }
+        }
+
+        if input.mouse_down() {
+            if let Some(index) = hovered {
+                return self.activate(index);
             }
         }
-    
-        if self.easy_selected {
-            return Some("easy");
-        } else if self.hard_selected {
-            return Some("hard");
+
+        if input.is_pressed(Action::Confirm) {
+            if let Some(index) = self.selected {
+                return self.activate(index);
+            }
         }
-    
+
         None
     }
-    
 
-    fn is_mouse_over_easy(&self, x: usize, y: usize) -> bool {
-        
-        x > 200 && x < 400 && y > 300 && y < 350
+    fn activate(&mut self, index: usize) -> Option<MenuAction> {
+        self.selected = Some(index);
+        let button = &self.buttons[index];
+        println!("Seleccionado: {}", button.label);
+        button.action.clone()
     }
 
-    fn is_mouse_over_hard(&self, x: usize, y: usize) -> bool {
-        
-        x > 500 && x < 700 && y > 300 && y < 350
+    pub fn render(&self, framebuffer: &mut Framebuffer) {
+        let selected = self.selected.map(|index| &self.buttons[index]);
+        let image = selected.and_then(|button| button.highlight.as_ref()).unwrap_or(&self.background);
+
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        let options = BlitOptions {
+            filter: Filter::Bilinear,
+            ..BlitOptions::default()
+        };
+        framebuffer.blit(image, image.bounds(), Rect::new(0, 0, width, height), &options);
+
+        let scale_x = width as f32 / self.background.width as f32;
+        let scale_y = height as f32 / self.background.height as f32;
+        let screen_bounds = |button: &Button| {
+            let (bx, by, bw, bh) = button.bounds;
+            (
+                (bx as f32 * scale_x) as usize,
+                (by as f32 * scale_y) as usize,
+                ((bx + bw) as f32 * scale_x) as usize,
+                ((by + bh) as f32 * scale_y) as usize,
+            )
+        };
+
+        // Buttons with a highlight image have their label painted into the
+        // background already.
+        for (index, button) in self.buttons.iter().enumerate().filter(|(_, b)| b.highlight.is_none()) {
+            let (x0, y0, x1, y1) = screen_bounds(button);
+            let scale = ((y1 - y0) / 22).max(1);
+            let style = TextStyle {
+                color: if self.selected == Some(index) { OUTLINE_COLOR } else { LABEL_COLOR },
+                scale,
+                align: Align::Center,
+                ..TextStyle::default()
+            };
+            let text_top = (y0 + y1).saturating_sub(11 * scale) / 2;
+            framebuffer.draw_text((x0 + x1) / 2, text_top, &button.label, &style);
+        }
+
+        if let Some(button) = selected.filter(|button| button.highlight.is_none()) {
+            let (x0, y0, x1, y1) = screen_bounds(button);
+
+            framebuffer.set_blend_mode(BlendMode::Additive);
+            framebuffer.set_current_color(GLOW_COLOR);
+            framebuffer.fill_rect(x0, y0, x1 - x0, y1 - y0);
+            framebuffer.set_blend_mode(BlendMode::Normal);
+
+            framebuffer.set_current_color(OUTLINE_COLOR);
+            for x in x0..x1 {
+                for y in [y0, y0 + 1, y1.saturating_sub(2), y1.saturating_sub(1)] {
+                    framebuffer.point(x, y);
+                }
+            }
+            for y in y0..y1 {
+                for x in [x0, x0 + 1, x1.saturating_sub(2), x1.saturating_sub(1)] {
+                    framebuffer.point(x, y);
+                }
+            }
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputFrame, ScriptedInput};
 
-fn load_texture(path: &str) -> (Vec<u32>, usize, usize) {
-    let img = image::open(path).expect("Failed to load texture");
-    let (width, height) = img.dimensions();
-    let data = img.to_rgba8().into_raw();
-    let texture: Vec<u32> = data.chunks(4).map(|p| {
-        let r = p[0] as u32;
-        let g = p[1] as u32;
-        let b = p[2] as u32;
-        let a = p[3] as u32;
-        (a << 24) | (r << 16) | (g << 8) | b
-    }).collect();
-    (texture, width as usize, height as usize)
-}
+    fn actions(menu: &mut Menu, frames: Vec<InputFrame>) -> Vec<MenuAction> {
+        let mut input = ScriptedInput::new(frames);
+        let mut chosen = Vec::new();
+        while input.advance() {
+            chosen.extend(menu.update(&input));
+        }
+        chosen
+    }
+
+    fn pressing(action: Action) -> InputFrame {
+        InputFrame {
+            pressed: vec![action],
+            window_size: (900, 600),
+            ..InputFrame::default()
+        }
+    }
 
+    #[test]
+    fn arrows_and_enter_pick_a_button() {
+        let mut menu = Menu::load("./main.menu").unwrap();
+        let chosen = actions(
+            &mut menu,
+            vec![pressing(Action::MenuNext), pressing(Action::MenuNext), pressing(Action::Confirm)],
+        );
+        assert!(matches!(chosen.as_slice(), [MenuAction::Play(path)] if path.ends_with("maze_hard.txt")));
 
+        let chosen = actions(&mut menu, vec![pressing(Action::MenuPrevious), pressing(Action::Confirm)]);
+        assert!(matches!(chosen.as_slice(), [MenuAction::Play(path)] if path.ends_with("maze_easy.txt")));
+    }
+
+    #[test]
+    fn clicks_are_scaled_from_window_to_background() {
+        let mut menu = Menu::load("./main.menu").unwrap();
+        // The random button is centered at (400, 267) of the 800x398
+        // background, which is stretched over a 900x600 window.
+        let click = InputFrame {
+            mouse_delta: (1.0, 0.0),
+            mouse_pos: Some((450.0, 403.0)),
+            mouse_down: true,
+            window_size: (900, 600),
+            ..InputFrame::default()
+        };
+        assert_eq!(actions(&mut menu, vec![click]), vec![MenuAction::Random]);
+    }
+}
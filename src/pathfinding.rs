@@ -0,0 +1,105 @@
+use nalgebra_glm::Vec2;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::maze::Maze;
+
+pub type Cell = (usize, usize);
+
+fn rebuild(came_from: &[Option<Cell>], width: usize, start: Cell, goal: Cell) -> Vec<Cell> {
+    let mut path = vec![goal];
+    let mut cell = goal;
+    while cell != start {
+        cell = came_from[cell.1 * width + cell.0].unwrap();
+        path.push(cell);
+    }
+    path.reverse();
+    path
+}
+
+/// Shortest path between two open cells, both ends included.
+pub fn bfs(maze: &Maze, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+    let width = maze.width();
+    let mut came_from = vec![None; width * maze.height()];
+    let mut visited = vec![false; width * maze.height()];
+    let mut queue = VecDeque::from([start]);
+    visited[start.1 * width + start.0] = true;
+
+    while let Some(cell) = queue.pop_front() {
+        if cell == goal {
+            return Some(rebuild(&came_from, width, start, goal));
+        }
+        for next in maze.neighbors(cell.0, cell.1) {
+            let index = next.1 * width + next.0;
+            if !visited[index] {
+                visited[index] = true;
+                came_from[index] = Some(cell);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+/// Same result as `bfs`, guided by the Manhattan distance to `goal`.
+pub fn astar(maze: &Maze, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+    let width = maze.width();
+    let heuristic = |(col, row): Cell| col.abs_diff(goal.0) + row.abs_diff(goal.1);
+
+    let mut came_from = vec![None; width * maze.height()];
+    let mut cost = vec![usize::MAX; width * maze.height()];
+    let mut open = BinaryHeap::from([Reverse((heuristic(start), start))]);
+    cost[start.1 * width + start.0] = 0;
+
+    while let Some(Reverse((_, cell))) = open.pop() {
+        if cell == goal {
+            return Some(rebuild(&came_from, width, start, goal));
+        }
+        let next_cost = cost[cell.1 * width + cell.0] + 1;
+        for next in maze.neighbors(cell.0, cell.1) {
+            let index = next.1 * width + next.0;
+            if next_cost < cost[index] {
+                cost[index] = next_cost;
+                came_from[index] = Some(cell);
+                open.push(Reverse((next_cost + heuristic(next), next)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Route from the cell containing `pos` to the goal `'F'`, or `None` when
+/// `pos` is inside a wall or the goal cannot be reached.
+pub fn path_to_goal(maze: &Maze, pos: Vec2) -> Option<Vec<Cell>> {
+    let (col, row) = maze.cell_coords(pos);
+    if maze.is_solid(col, row) {
+        return None;
+    }
+    astar(maze, (col as usize, row as usize), maze.goal()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::parse_maze;
+
+    const FIXTURE: &str = "+------+\n|@   | |\n|--- | |\n|    | |\n| ---  |\n|     F|\n+------+";
+
+    #[test]
+    fn bfs_and_astar_find_equally_short_paths() {
+        let maze = parse_maze(FIXTURE, 55).unwrap().maze;
+        let goal = maze.goal().unwrap();
+
+        let breadth = bfs(&maze, (1, 1), goal).unwrap();
+        let guided = astar(&maze, (1, 1), goal).unwrap();
+
+        assert_eq!(breadth.len(), 16);
+        assert_eq!(guided.len(), breadth.len());
+        for path in [&breadth, &guided] {
+            assert_eq!((path[0], path[path.len() - 1]), ((1, 1), goal));
+            assert!(path.windows(2).all(|step| step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1) == 1));
+        }
+    }
+}
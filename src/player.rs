@@ -1,8 +1,47 @@
-
 use nalgebra_glm::{Vec2};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Player {
     pub pos: Vec2,
     pub a: f32, 
     pub fov: f32, 
-}
\ No newline at end of file
+    pub radius: f32,
+    /// Units per second.
+    pub vel: Vec2,
+    /// Sprint left, from 0 (exhausted) to 1 (full).
+    pub stamina: f32,
+    /// Set when the stamina runs out; sprinting waits until it recovers.
+    pub exhausted: bool,
+    /// Radians above (positive) or below the horizon.
+    pub pitch: f32,
+    /// Height of a jump above the ground and its vertical speed.
+    pub z: f32,
+    pub vz: f32,
+    /// How far crouching lowers the eye.
+    pub crouch: f32,
+}
+
+impl Player {
+    pub fn lerp(&self, next: &Player, t: f32) -> Player {
+        Player {
+            pos: self.pos + (next.pos - self.pos) * t,
+            a: self.a + (next.a - self.a) * t,
+            pitch: self.pitch + (next.pitch - self.pitch) * t,
+            z: self.z + (next.z - self.z) * t,
+            crouch: self.crouch + (next.crouch - self.crouch) * t,
+            ..*next
+        }
+    }
+
+    /// Height of the eye above the floor. Standing, it is halfway up a wall.
+    pub fn eye_height(&self, block_size: usize) -> f32 {
+        block_size as f32 / 2.0 + self.z - self.crouch
+    }
+
+    /// Screen row of the horizon. Pitch shears the view vertically instead of
+    /// tilting the camera, so walls stay upright. 200 is the renderers'
+    /// distance to the projection plane.
+    pub fn horizon(&self, screen_height: usize) -> f32 {
+        screen_height as f32 / 2.0 + self.pitch.tan() * 200.0
+    }
+}
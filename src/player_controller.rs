@@ -1,72 +1,120 @@
-use minifb::Window;
-use minifb::Key;
+use crate::bindings::{Action, MouseMode, MouseSettings};
+use crate::input::InputSource;
 use crate::player::Player;
+use crate::maze::{friction, Maze};
 use rodio::Sink;
+use nalgebra_glm::Vec2;
+
+/// Mouse movement gathered once per rendered frame and spent by
+/// the next simulation step, so turning does not depend on how many steps
+/// a frame runs.
+pub struct MouseLook {
+    pending: (f32, f32),
+}
+
+impl MouseLook {
+    pub fn new() -> MouseLook {
+        MouseLook { pending: (0.0, 0.0) }
+    }
+
+    pub fn sample(&mut self, input: &dyn InputSource) {
+        let (dx, dy) = input.mouse_delta();
+        self.pending.0 += dx;
+        self.pending.1 += dy;
+    }
+
+    fn take(&mut self) -> (f32, f32) {
+        std::mem::take(&mut self.pending)
+    }
+}
 
 pub fn process_events(
-    window: &mut Window,
+    input: &dyn InputSource,
     player: &mut Player,
-    maze: &Vec<Vec<char>>,
-    block_size: usize,
-    walking_sound_sink: &Sink, 
+    maze: &Maze,
+    mouse: &MouseSettings,
+    mouse_look: &mut MouseLook,
+    walking_sound_sink: Option<&Sink>,
+    delta: f32,
 ) {
-    const MOVE_SPEED: f32 = 5.0;
-    const ROTATION_SPEED: f32 = 0.3;
+    // Radians per second.
+    const ROTATION_SPEED: f32 = 3.0;
     const DEAD_ZONE: f32 = 250.0;
+    const PITCH_SPEED: f32 = 1.5;
+    const MAX_PITCH: f32 = 0.6;
 
-    
-    if let Some((mouse_x, _)) = window.get_mouse_pos(minifb::MouseMode::Discard) {
-        let center_x = window.get_size().0 as f32 / 2.0;
+    let (mouse_dx, mouse_dy) = mouse_look.take();
+    // Inverting only flips looking up and down.
+    let invert = if mouse.invert { -1.0 } else { 1.0 };
+
+    if mouse.mode == MouseMode::Relative {
+        player.a += mouse_dx * mouse.sensitivity;
+        player.pitch -= mouse_dy * mouse.sensitivity * invert;
+    } else if let Some((mouse_x, _)) = input.mouse_pos() {
+        let window_width = input.window_size().0 as f32;
+        let center_x = window_width / 2.0;
         let left_dead_zone = center_x - DEAD_ZONE / 2.0;
         let right_dead_zone = center_x + DEAD_ZONE / 2.0;
 
         if mouse_x < left_dead_zone {
-            player.a -= ROTATION_SPEED * (left_dead_zone - mouse_x) / left_dead_zone;
+            player.a -= ROTATION_SPEED * delta * (left_dead_zone - mouse_x) / left_dead_zone;
         } else if mouse_x > right_dead_zone {
-            player.a += ROTATION_SPEED * (mouse_x - right_dead_zone) / (window.get_size().0 as f32 - right_dead_zone);
+            player.a += ROTATION_SPEED * delta * (mouse_x - right_dead_zone) / (window_width - right_dead_zone);
         }
     }
 
     
-    let forward_x = player.a.cos() * MOVE_SPEED;
-    let forward_y = player.a.sin() * MOVE_SPEED;
+    let forward = Vec2::new(player.a.cos(), player.a.sin());
+    let right = Vec2::new(-player.a.sin(), player.a.cos());
 
-    let mut new_x = player.pos.x;
-    let mut new_y = player.pos.y;
+    let mut direction = Vec2::new(0.0, 0.0);
 
-    let mut is_moving = false;
-
-    if window.is_key_down(Key::Up) {
-        new_x += forward_x; 
-        new_y += forward_y; 
-        is_moving = true;
+    if input.is_down(Action::MoveForward) {
+        direction += forward;
+    }
+    if input.is_down(Action::MoveBack) {
+        direction -= forward;
+    }
+    if input.is_down(Action::StrafeRight) {
+        direction += right;
     }
-    if window.is_key_down(Key::Down) {
-        new_x -= forward_x; 
-        new_y -= forward_y; 
-        is_moving = true;
+    if input.is_down(Action::StrafeLeft) {
+        direction -= right;
     }
 
-    if window.is_key_down(Key::Left) {
-        player.a -= ROTATION_SPEED; 
+    if input.is_down(Action::TurnLeft) {
+        player.a -= ROTATION_SPEED * delta; 
+    }
+    if input.is_down(Action::TurnRight) {
+        player.a += ROTATION_SPEED * delta; 
+    }
+    if input.is_down(Action::LookUp) {
+        player.pitch += PITCH_SPEED * delta;
     }
-    if window.is_key_down(Key::Right) {
-        player.a += ROTATION_SPEED; 
+    if input.is_down(Action::LookDown) {
+        player.pitch -= PITCH_SPEED * delta;
     }
+    player.pitch = player.pitch.clamp(-MAX_PITCH, MAX_PITCH);
 
-    
-    
-let i = new_x as usize / block_size;
-let j = new_y as usize / block_size;
+    update_height(input, player, delta);
 
+    // Moving diagonally is no faster than moving straight.
+    let wish = if direction.magnitude() > 0.0 { direction.normalize() } else { direction };
+    update_velocity(input, player, maze, wish, delta);
 
-if maze[j][i] == ' ' || maze[j][i] == 'F' {
-    player.pos.x = new_x;
-    player.pos.y = new_y;
-}
+    let previous = player.pos;
+    move_player(player, maze, player.vel * delta);
+    // Whatever a wall stopped is lost, so speed does not build up against it.
+    if delta > 0.0 {
+        player.vel = (player.pos - previous) / delta;
+    }
 
+    // Sliding on ice with the keys released still sounds like walking.
+    let is_moving = player.vel.magnitude() > 10.0;
 
-    
+    let Some(walking_sound_sink) = walking_sound_sink else {
+        return;
+    };
     if is_moving {
         if walking_sound_sink.is_paused() {
             walking_sound_sink.play();
@@ -77,3 +125,241 @@ if maze[j][i] == ' ' || maze[j][i] == 'F' {
         }
     }
 }
+
+/// Eases the velocity towards `wish` (a unit vector, or zero to stop) at top
+/// speed, faster the more friction the floor has. Ice keeps the top speed but
+/// is slow to start, turn and stop; mud lowers the top speed.
+fn update_velocity(input: &dyn InputSource, player: &mut Player, maze: &Maze, wish: Vec2, delta: f32) {
+    // Top speed on plain ground in units per second, and the rate per second
+    // at which plain ground closes the gap to it.
+    const MOVE_SPEED: f32 = 150.0;
+    const SPRINT_FACTOR: f32 = 1.7;
+    const FRICTION: f32 = 10.0;
+    // Seconds to empty and to refill the stamina meter.
+    const SPRINT_DURATION: f32 = 3.0;
+    const RECOVERY_DURATION: f32 = 5.0;
+    // Stamina an exhausted player needs back before sprinting again.
+    const RECOVERED: f32 = 0.25;
+
+    let sprinting = input.is_down(Action::Sprint) && wish.magnitude() > 0.0 && !player.exhausted;
+    if sprinting {
+        player.stamina = (player.stamina - delta / SPRINT_DURATION).max(0.0);
+        player.exhausted = player.stamina == 0.0;
+    } else {
+        player.stamina = (player.stamina + delta / RECOVERY_DURATION).min(1.0);
+        player.exhausted &= player.stamina < RECOVERED;
+    }
+
+    let friction = friction(maze.cell_at_world(player.pos));
+    let top_speed = if sprinting { MOVE_SPEED * SPRINT_FACTOR } else { MOVE_SPEED };
+
+    let target = wish * top_speed * friction.min(1.0) / friction;
+    let blend = 1.0 - (-FRICTION * friction * delta).exp();
+    player.vel += (target - player.vel) * blend;
+}
+
+/// Jumping and crouching only move the eye; collisions stay in the plane.
+/// Holding jump keeps hopping, since a press can fall between two steps.
+fn update_height(input: &dyn InputSource, player: &mut Player, delta: f32) {
+    // Units per second (squared for gravity) and units.
+    const JUMP_SPEED: f32 = 150.0;
+    const GRAVITY: f32 = 800.0;
+    const CROUCH_DEPTH: f32 = 12.0;
+    const CROUCH_SPEED: f32 = 80.0;
+
+    if player.z <= 0.0 && input.is_down(Action::Jump) {
+        player.vz = JUMP_SPEED;
+    }
+    player.vz -= GRAVITY * delta;
+    player.z += player.vz * delta;
+    if player.z <= 0.0 {
+        player.z = 0.0;
+        player.vz = 0.0;
+    }
+
+    let target = if input.is_down(Action::Crouch) { CROUCH_DEPTH } else { 0.0 };
+    let step = CROUCH_SPEED * delta;
+    player.crouch += (target - player.crouch).clamp(-step, step);
+}
+
+/// Moves one axis at a time so that blocked motion along one axis still lets
+/// the player slide along the other. Long steps are split so a slow frame
+/// cannot carry the player through a wall.
+pub fn move_player(player: &mut Player, maze: &Maze, step: Vec2) {
+    let substeps = (step.magnitude() / (player.radius * 0.5)).ceil().max(1.0) as usize;
+    let step = step / substeps as f32;
+
+    for _ in 0..substeps {
+        for axis_step in [Vec2::new(step.x, 0.0), Vec2::new(0.0, step.y)] {
+            let previous = player.pos;
+            player.pos += axis_step;
+            if !push_out_of_walls(maze, &mut player.pos, player.radius) {
+                player.pos = previous;
+            }
+        }
+    }
+}
+
+/// Pushes a circle out of every solid cell it overlaps, along the line to the
+/// closest point of that cell, which rounds off wall corners. Returns `false`
+/// if the center itself ended up inside a wall. Cells outside the maze are
+/// solid, so the border behaves like any other wall.
+fn push_out_of_walls(maze: &Maze, pos: &mut Vec2, radius: f32) -> bool {
+    let block = maze.block_size() as f32;
+    let (col, row) = maze.cell_coords(*pos);
+
+    for r in row - 1..=row + 1 {
+        for c in col - 1..=col + 1 {
+            if !maze.is_solid(c, r) {
+                continue;
+            }
+
+            let min = Vec2::new(c as f32 * block, r as f32 * block);
+            let closest = Vec2::new(pos.x.clamp(min.x, min.x + block), pos.y.clamp(min.y, min.y + block));
+            let offset = *pos - closest;
+            let distance = offset.magnitude();
+
+            if distance == 0.0 {
+                return false;
+            }
+            if distance < radius {
+                *pos += offset * ((radius - distance) / distance);
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::spawn_player;
+    use crate::input::{InputFrame, ScriptedInput};
+    use crate::maze::{load_maze, parse_maze};
+
+    fn holding(actions: &[Action], frames: usize) -> ScriptedInput {
+        let frame = InputFrame {
+            dt: 1.0 / 60.0,
+            down: actions.to_vec(),
+            ..InputFrame::default()
+        };
+        ScriptedInput::new(vec![frame; frames])
+    }
+
+    fn mouse() -> MouseSettings {
+        MouseSettings {
+            mode: MouseMode::Relative,
+            sensitivity: 0.004,
+            invert: false,
+        }
+    }
+
+    #[test]
+    fn exhausted_player_waits_for_stamina_before_sprinting_again() {
+        let level = load_maze("./maze_easy.txt", 55).unwrap();
+        let mut player = spawn_player(&level);
+        let mut mouse_look = MouseLook::new();
+        // Twenty seconds of running into whatever is ahead with Shift held.
+        let mut input = holding(&[Action::MoveForward, Action::Sprint], 1200);
+
+        let mut stamina = vec![player.stamina];
+        while input.advance() {
+            process_events(&input, &mut player, &level.maze, &mouse(), &mut mouse_look, None, 1.0 / 60.0);
+            stamina.push(player.stamina);
+        }
+
+        let empty = stamina.iter().position(|&s| s == 0.0).expect("la resistencia nunca se agotó");
+        let resumed = (empty + 1..stamina.len())
+            .find(|&i| stamina[i] < stamina[i - 1])
+            .expect("nunca volvió a correr");
+        assert!(stamina[resumed - 1] >= 0.25, "volvió a correr con {}", stamina[resumed - 1]);
+        assert!(stamina[empty..resumed].windows(2).all(|w| w[1] > w[0]));
+    }
+
+    #[test]
+    fn held_keys_move_and_turn_the_player() {
+        let level = load_maze("./maze_easy.txt", 55).unwrap();
+        let mut player = spawn_player(&level);
+        let start = player;
+        let mut mouse_look = MouseLook::new();
+
+        let mut input = holding(&[Action::TurnRight], 30);
+        while input.advance() {
+            process_events(&input, &mut player, &level.maze, &mouse(), &mut mouse_look, None, 1.0 / 60.0);
+        }
+        assert!((player.a - start.a - 1.5).abs() < 1e-4);
+        assert_eq!(player.pos, start.pos);
+
+        let mut input = holding(&[Action::MoveBack], 10);
+        while input.advance() {
+            process_events(&input, &mut player, &level.maze, &mouse(), &mut mouse_look, None, 1.0 / 60.0);
+        }
+        let moved = player.pos - start.pos;
+        let facing = Vec2::new(player.a.cos(), player.a.sin());
+        assert!(moved.dot(&facing) < 0.0, "no retrocedió: {:?}", moved);
+    }
+
+    #[test]
+    fn invert_only_flips_pitch() {
+        let level = load_maze("./maze_easy.txt", 55).unwrap();
+        let look = |invert: bool| {
+            let mut player = spawn_player(&level);
+            let mut mouse_look = MouseLook::new();
+            let mut input = ScriptedInput::new(vec![InputFrame { mouse_delta: (20.0, 10.0), ..InputFrame::default() }]);
+            input.advance();
+            mouse_look.sample(&input);
+            let mouse = MouseSettings { invert, ..mouse() };
+            process_events(&input, &mut player, &level.maze, &mouse, &mut mouse_look, None, 1.0 / 60.0);
+            (player.a, player.pitch)
+        };
+
+        let (yaw, pitch) = look(false);
+        let (inverted_yaw, inverted_pitch) = look(true);
+        assert_eq!(yaw, inverted_yaw);
+        assert!(pitch < 0.0);
+        assert_eq!(inverted_pitch, -pitch);
+    }
+
+    fn player_in(maze: &Maze, col: usize, row: usize) -> Player {
+        Player {
+            pos: maze.cell_center(col, row),
+            radius: 3.0,
+            ..spawn_player(&parse_maze("+---+\n|@ F|\n+---+", 10).unwrap())
+        }
+    }
+
+    #[test]
+    fn diagonal_step_into_a_wall_slides_along_it() {
+        let maze = parse_maze("+----+\n|@   |\n|    |\n|   F|\n+----+", 10).unwrap().maze;
+        let mut player = player_in(&maze, 1, 2);
+
+        move_player(&mut player, &maze, Vec2::new(-8.0, 2.0));
+
+        assert!((player.pos.x - (10.0 + player.radius)).abs() < 1e-3, "{:?}", player.pos);
+        assert!((player.pos.y - 27.0).abs() < 1e-3, "{:?}", player.pos);
+    }
+
+    #[test]
+    fn large_step_does_not_tunnel_through_a_wall() {
+        let maze = parse_maze("+----+\n|@| F|\n|    |\n+----+", 10).unwrap().maze;
+        let mut player = player_in(&maze, 1, 1);
+
+        // Lands in the open cell past the wall if taken in one go.
+        move_player(&mut player, &maze, Vec2::new(20.0, 0.0));
+
+        assert!(player.pos.x <= 20.0 - player.radius + 1e-3, "{:?}", player.pos);
+    }
+
+    #[test]
+    fn grid_border_stops_the_player_inside() {
+        let maze = Maze::new(vec![vec![' '; 3]; 3], 10);
+        let mut player = player_in(&maze, 0, 0);
+
+        move_player(&mut player, &maze, Vec2::new(-100.0, -100.0));
+        assert!(player.pos.x >= player.radius - 1e-3 && player.pos.y >= player.radius - 1e-3, "{:?}", player.pos);
+
+        move_player(&mut player, &maze, Vec2::new(100.0, 100.0));
+        assert!(player.pos.x <= 30.0 - player.radius + 1e-3 && player.pos.y <= 30.0 - player.radius + 1e-3);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::caster::cast_ray;
+use crate::framebuffer::{BlitOptions, Framebuffer, Rect, TextStyle};
+use crate::maze::Maze;
+use crate::pathfinding::Cell;
+use crate::player::Player;
+use crate::sprite::{render_sprite_icons, Sprite, SpriteTextures};
+use crate::surface::Surfaces;
+use crate::texture::{Texture, TextureRegistry};
+
+const HINT_COLOR: u32 = 0xFFD700;
+const BREADCRUMB_RADIUS: f32 = 0.1;
+const STAMINA_COLOR: u32 = 0x4CAF50;
+const STAMINA_LOW_COLOR: u32 = 0xFF9800;
+pub const PANEL_COLOR: u32 = 0x000000;
+pub const PANEL_OPACITY: f32 = 0.5;
+const MINIMAP_BORDER_COLOR: u32 = 0xDDDDDD;
+const MINIMAP_BORDER_WIDTH: usize = 2;
+
+
+pub fn render_2d(
+    framebuffer: &mut Framebuffer,
+    player: &Player,
+    maze: &Maze,
+    textures: &TextureRegistry,
+    sprites: &[Sprite],
+    sprite_textures: &SpriteTextures,
+    hint: Option<&[Cell]>
+) {
+    let block_size = maze.block_size();
+
+    for (col_index, row_index, cell) in maze.cells() {
+        let (col, row) = (col_index as isize, row_index as isize);
+        if cell == Maze::OUTSIDE || !(maze.is_goal(col, row) || maze.is_solid(col, row)) {
+            continue;
+        }
+
+        let texture = textures.cell(cell);
+        let tile = Rect::new((col_index * block_size) as isize, (row_index * block_size) as isize, block_size, block_size);
+        framebuffer.blit(texture, texture.bounds(), tile, &BlitOptions::default());
+    }
+
+    if let Some(path) = hint {
+        render_path(framebuffer, maze, path, 1);
+    }
+
+    render_sprite_icons(framebuffer, sprites, sprite_textures, maze.block_size(), 1);
+
+    framebuffer.set_current_color(0xFFDDD);
+
+    
+    let player_size = 5;
+
+    
+    let x = (player.pos.x as usize).saturating_sub(player_size);
+    let y = (player.pos.y as usize).saturating_sub(player_size);
+    let side = (player.pos.x as usize + player_size + 1 - x, player.pos.y as usize + player_size + 1 - y);
+    framebuffer.fill_rect(x, y, side.0, side.1);
+}
+
+
+
+pub fn render3d(
+    framebuffer: &mut Framebuffer,
+    player: &Player,
+    maze: &Maze,
+    textures: &TextureRegistry,
+    hint: Option<&[Cell]>,
+    fisheye: bool
+) -> Vec<f32> {
+    let num_rays = framebuffer.width;
+
+    let horizon = player.horizon(framebuffer.height);
+
+    
+    let block_size = maze.block_size();
+    let eye = player.eye_height(block_size);
+
+    render_floor_and_ceiling(framebuffer, player, maze, &textures.surfaces, hint, fisheye);
+
+    let mut depth_buffer = vec![f32::INFINITY; num_rays];
+    let min_distance = block_size as f32 * 200.0 / (4 * framebuffer.height.max(1)) as f32;
+
+    for (i, depth) in depth_buffer.iter_mut().enumerate() {
+        let a = ray_angle(player, i, num_rays, fisheye);
+
+        let intersect = cast_ray(framebuffer, maze, player, a, false);
+
+        let distance = if fisheye {
+            intersect.distance
+        } else {
+            intersect.distance * (a - player.a).cos()
+        };
+        *depth = distance;
+
+        // Walls right against the camera would project to huge or infinite
+        // spans; a few screens tall is already taller than anything visible.
+        let distance = distance.max(min_distance);
+        let wall_height = (block_size as f32 / distance) * 200.0;
+
+        let y0 = horizon - ((block_size as f32 - eye) / distance) * 200.0;
+
+        let wall_x = intersect.wall_offset(block_size);
+
+        let wall_texture = textures.cell(intersect.impact);
+        let texture_x = (wall_x * wall_texture.width as f32) as usize;
+
+        framebuffer.texture_span(i, y0, wall_height, wall_texture, texture_x);
+    }
+
+    depth_buffer
+}
+
+
+fn render_floor_and_ceiling(
+    framebuffer: &mut Framebuffer,
+    player: &Player,
+    maze: &Maze,
+    surfaces: &Surfaces,
+    hint: Option<&[Cell]>,
+    fisheye: bool
+) {
+    let block = maze.block_size() as f32;
+    let horizon = player.horizon(framebuffer.height);
+    let eye = player.eye_height(maze.block_size());
+
+    let mut on_path = vec![false; maze.width() * maze.height()];
+    for &(col, row) in hint.unwrap_or_default() {
+        on_path[row * maze.width() + col] = true;
+    }
+
+    // World offset covered by one unit of row depth, per column.
+    let rays: Vec<(f32, f32)> = (0..framebuffer.width)
+        .map(|i| {
+            let a = ray_angle(player, i, framebuffer.width, fisheye);
+            let scale = if fisheye { 1.0 } else { 1.0 / (a - player.a).cos() };
+            (a.cos() * scale, a.sin() * scale)
+        })
+        .collect();
+
+    let mut row_pixels = vec![0; framebuffer.width];
+    for y in 0..framebuffer.height {
+        let dy = y as f32 + 0.5 - horizon;
+        let is_floor = dy > 0.0;
+        let height = if is_floor { eye } else { block - eye };
+        let row_depth = (height / dy.abs()) * 200.0;
+
+        // Neighboring pixels mostly share a cell, so its surface is only
+        // looked up again when the cell changes.
+        let mut last_cell = None;
+        let mut texture = None;
+
+        for (pixel, &(rx, ry)) in row_pixels.iter_mut().zip(&rays) {
+            let wx = player.pos.x + rx * row_depth;
+            let wy = player.pos.y + ry * row_depth;
+
+            let (cx, cy) = (wx / block, wy / block);
+            let (col, row) = (cx.floor(), cy.floor());
+            let (u, v) = (cx - col, cy - row);
+            let (col, row) = (col as isize, row as isize);
+            let cell = maze.cell(col, row);
+
+            if is_floor && maze.contains(col, row) && on_path[row as usize * maze.width() + col as usize] {
+                let (du, dv) = (u - 0.5, v - 0.5);
+                if du * du + dv * dv < BREADCRUMB_RADIUS * BREADCRUMB_RADIUS {
+                    *pixel = HINT_COLOR;
+                    continue;
+                }
+            }
+
+            if last_cell != Some(cell) {
+                last_cell = Some(cell);
+                texture = if is_floor {
+                    Some(surfaces.floor_at(cell))
+                } else {
+                    surfaces.ceiling_at(cell)
+                };
+            }
+
+            *pixel = match texture {
+                Some(texture) => texture.sample(u, v),
+                None => surfaces.sky_color,
+            };
+        }
+
+        framebuffer.copy_row(0, y, &row_pixels);
+    }
+}
+
+
+fn render_path(framebuffer: &mut Framebuffer, maze: &Maze, path: &[Cell], scale: usize) {
+    framebuffer.set_current_color(HINT_COLOR);
+
+    let dot = (maze.block_size() / scale / 10).max(1);
+    for pair in path.windows(2) {
+        let from = maze.cell_center(pair[0].0, pair[0].1) / scale as f32;
+        let to = maze.cell_center(pair[1].0, pair[1].1) / scale as f32;
+        let steps = (to - from).magnitude().ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let p = from + (to - from) * (step as f32 / steps as f32);
+            framebuffer.fill_rect((p.x as usize).saturating_sub(dot / 2), (p.y as usize).saturating_sub(dot / 2), dot, dot);
+        }
+    }
+}
+
+
+fn ray_angle(player: &Player, column: usize, num_rays: usize, fisheye: bool) -> f32 {
+    let current_ray = column as f32 / num_rays as f32;
+    if fisheye {
+        player.a - (player.fov / 2.0) + (player.fov * current_ray)
+    } else {
+        let plane_x = (2.0 * current_ray - 1.0) * (player.fov / 2.0).tan();
+        player.a + plane_x.atan()
+    }
+}
+
+
+/// How far a `view` pixels wide window into a `map` pixels wide minimap is
+/// scrolled to center the player at world coordinate `player`.
+fn minimap_scroll(player: f32, view: usize, map: usize, minimap_scale: usize) -> isize {
+    (player as isize / minimap_scale as isize - view as isize / 2).clamp(0, (map - view) as isize)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_minimap(
+    framebuffer: &mut Framebuffer,
+    player: &Player,
+    maze: &Maze,
+    textures: &TextureRegistry,
+    sprites: &[Sprite],
+    sprite_textures: &SpriteTextures,
+    hint: Option<&[Cell]>,
+    minimap_scale: usize,
+    frame: Rect,
+) {
+    let block_size = maze.block_size();
+    let scaled_block_size = block_size / minimap_scale;
+
+    // Small mazes shrink the frame; large ones scroll to keep the player in
+    // the middle, stopping at the maze's edges.
+    let map_width = maze.width() * scaled_block_size;
+    let map_height = maze.height() * scaled_block_size;
+    let (width, height) = (frame.width.min(map_width), frame.height.min(map_height));
+    let scroll_x = minimap_scroll(player.pos.x, width, map_width, minimap_scale);
+    let scroll_y = minimap_scroll(player.pos.y, height, map_height, minimap_scale);
+
+    let border = MINIMAP_BORDER_WIDTH;
+    framebuffer.push_viewport(Rect::new(
+        frame.x - border as isize,
+        frame.y - border as isize,
+        width + 2 * border,
+        height + 2 * border,
+    ));
+    framebuffer.set_current_color(MINIMAP_BORDER_COLOR);
+    framebuffer.fill_rect(0, 0, width + 2 * border, border);
+    framebuffer.fill_rect(0, height + border, width + 2 * border, border);
+    framebuffer.fill_rect(0, border, border, height);
+    framebuffer.fill_rect(width + border, border, border, height);
+
+    framebuffer.push_viewport(Rect::new(border as isize, border as isize, width, height));
+    framebuffer.set_current_color(PANEL_COLOR);
+    framebuffer.set_opacity(PANEL_OPACITY);
+    framebuffer.fill();
+    framebuffer.set_opacity(1.0);
+
+    framebuffer.push_viewport(Rect::new(-scroll_x, -scroll_y, map_width, map_height));
+
+    for (col_index, row_index, cell) in maze.cells() {
+        let (col, row) = (col_index as isize, row_index as isize);
+        if cell == Maze::OUTSIDE || !(maze.is_goal(col, row) || maze.is_solid(col, row)) {
+            continue;
+        }
+
+        let texture = textures.cell(cell);
+        let tile = Rect::new(
+            (col_index * scaled_block_size) as isize,
+            (row_index * scaled_block_size) as isize,
+            scaled_block_size,
+            scaled_block_size,
+        );
+        framebuffer.blit(texture, texture.bounds(), tile, &BlitOptions::default());
+    }
+
+    if let Some(path) = hint {
+        render_path(framebuffer, maze, path, minimap_scale);
+    }
+
+    render_sprite_icons(framebuffer, sprites, sprite_textures, maze.block_size(), minimap_scale);
+
+    framebuffer.set_current_color(0xFFDDD);
+
+    
+    let minimap_player_size = 2;
+
+    
+    let minimap_player_x = (player.pos.x as usize) / minimap_scale;
+    let minimap_player_y = (player.pos.y as usize) / minimap_scale;
+
+    let x = minimap_player_x.saturating_sub(minimap_player_size);
+    let y = minimap_player_y.saturating_sub(minimap_player_size);
+    framebuffer.fill_rect(
+        x,
+        y,
+        minimap_player_x + minimap_player_size + 1 - x,
+        minimap_player_y + minimap_player_size + 1 - y,
+    );
+
+    framebuffer.pop_viewport();
+    framebuffer.pop_viewport();
+    framebuffer.pop_viewport();
+}
+
+pub fn render_stamina(framebuffer: &mut Framebuffer, player: &Player) {
+    let (bar_width, bar_height) = (200, 10);
+    let x0 = 20;
+    let y0 = framebuffer.height.saturating_sub(30);
+    let filled = (player.stamina * bar_width as f32) as usize;
+
+    framebuffer.set_current_color(PANEL_COLOR);
+    framebuffer.set_opacity(PANEL_OPACITY);
+    framebuffer.fill_rect(x0 - 6, y0 - 20, bar_width + 12, bar_height + 26);
+    framebuffer.set_opacity(1.0);
+
+    framebuffer.draw_text(x0, y0.saturating_sub(14), "Resistencia", &TextStyle::default());
+
+    framebuffer.set_current_color(0x222222);
+    framebuffer.fill_rect(x0 + filled, y0, bar_width - filled.min(bar_width), bar_height);
+
+    framebuffer.set_current_color(if player.exhausted || player.stamina < 0.25 { STAMINA_LOW_COLOR } else { STAMINA_COLOR });
+    framebuffer.fill_rect(x0, y0, filled.min(bar_width), bar_height);
+}
+
+/// Draws `image` at its own size in the middle of the framebuffer, cropping
+/// whatever does not fit.
+pub fn draw_centered_image(framebuffer: &mut Framebuffer, image: &Texture) {
+    let x = (framebuffer.width as isize - image.width as isize) / 2;
+    let y = (framebuffer.height as isize - image.height as isize) / 2;
+    let destination = Rect::new(x, y, image.width, image.height);
+    framebuffer.blit(image, image.bounds(), destination, &BlitOptions::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::spawn_player;
+    use crate::maze::{load_maze, parse_maze};
+    use crate::sprite::render_sprites;
+    use nalgebra_glm::Vec2;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn centered_image_larger_than_the_framebuffer_is_cropped() {
+        let image = Texture {
+            data: (0..40).map(|n| 0xFF000000 | n).collect(),
+            width: 8,
+            height: 5,
+        };
+        let mut framebuffer = Framebuffer::new(4, 3);
+        draw_centered_image(&mut framebuffer, &image);
+
+        // Offset by (-2, -1): the framebuffer shows columns 2..6 of rows 1..4.
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(framebuffer.buffer[y * 4 + x], ((y + 1) * 8 + x + 2) as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn minimap_scroll_centers_the_player_until_an_edge() {
+        // A 40 pixel view into a 100 pixel map drawn at a fifth of the world.
+        assert_eq!(minimap_scroll(50.0, 40, 100, 5), 0);
+        assert_eq!(minimap_scroll(250.0, 40, 100, 5), 30);
+        assert_eq!(minimap_scroll(480.0, 40, 100, 5), 60);
+        assert_eq!(minimap_scroll(250.0, 100, 100, 5), 0);
+    }
+
+    #[test]
+    fn minimap_stays_inside_its_frame() {
+        let level = load_maze("./maze_easy.txt", 55).unwrap();
+        let textures = TextureRegistry::for_maze("./maze_easy.txt");
+        let sprite_textures = SpriteTextures::load();
+        let mut framebuffer = Framebuffer::new(100, 80);
+        let frame = Rect::new(10, 10, 40, 30);
+
+        for (x, y) in [(0.0, 0.0), (10_000.0, 10_000.0), (level.spawn.x, level.spawn.y)] {
+            let mut player = spawn_player(&level);
+            player.pos = Vec2::new(x, y);
+            render_minimap(&mut framebuffer, &player, &level.maze, &textures, &level.sprites, &sprite_textures, None, 5, frame);
+        }
+
+        assert_eq!((framebuffer.width, framebuffer.height), (100, 80));
+        let border = MINIMAP_BORDER_WIDTH;
+        for y in 0..80 {
+            for x in 0..100 {
+                let inside = (10 - border..50 + border).contains(&x) && (10 - border..40 + border).contains(&y);
+                assert!(inside || framebuffer.buffer[y * 100 + x] == 0, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn renders_a_frame_pressed_against_a_wall() {
+        let level = parse_maze("+----+\n|@  F|\n+----+", 55).unwrap();
+        let textures = TextureRegistry::for_maze("./maze_easy.txt");
+        let sprite_textures = SpriteTextures::load();
+        let mut player = spawn_player(&level);
+        player.pos.x = 55.0;
+        player.a = PI;
+
+        let mut framebuffer = Framebuffer::new(160, 120);
+        let depth_buffer = render3d(&mut framebuffer, &player, &level.maze, &textures, None, false);
+        render_sprites(&mut framebuffer, &player, &level.sprites, &sprite_textures, &depth_buffer, 55, false);
+
+        assert_eq!(depth_buffer.len(), 160);
+        assert!(depth_buffer.iter().all(|depth| !depth.is_nan()));
+        let wall = textures.cell('|');
+        assert!(framebuffer.buffer.iter().any(|&pixel| wall.data.contains(&(pixel | 0xFF000000))));
+    }
+}
@@ -0,0 +1,242 @@
+use rodio::OutputStreamHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bindings::{Action, MouseMode, MouseSettings};
+use crate::framebuffer::{Align, BlendMode, Framebuffer, TextStyle};
+use crate::game::Game;
+use crate::generator::{generate, Algorithm, GeneratorOptions};
+use crate::input::InputSource;
+use crate::menu::{Menu, MenuAction};
+use crate::render::draw_centered_image;
+use crate::texture::Texture;
+
+pub enum Scene {
+    Menu(Menu),
+    Playing(Box<Game>),
+    Paused,
+    /// Seconds the run took, and the level's par.
+    Victory { elapsed: f32, par: Option<f32> },
+    /// The level's time limit in seconds.
+    Defeat { limit: f32 },
+}
+
+impl Scene {
+    /// Overlays are drawn on top of the scenes below them instead of
+    /// replacing them.
+    fn is_overlay(&self) -> bool {
+        matches!(self, Scene::Paused | Scene::Defeat { .. })
+    }
+}
+
+pub enum Transition {
+    Stay,
+    Push(Scene),
+    Pop,
+    /// Back to a fresh start of the level being played.
+    Retry,
+    /// Drops everything above the menu.
+    ToMenu,
+    Quit,
+}
+
+/// Every screen of the game shares one window. Only the top scene receives
+/// input; the menu always stays at the bottom.
+pub struct SceneStack {
+    scenes: Vec<Scene>,
+    final_screen: Texture,
+    stream: OutputStreamHandle,
+    mouse: MouseSettings,
+    record_path: Option<String>,
+}
+
+fn generate_random_maze(path: &str) -> std::io::Result<()> {
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    let options = GeneratorOptions {
+        algorithm: Algorithm::ALL[(seed % Algorithm::ALL.len() as u64) as usize],
+        seed,
+        loop_density: 0.1,
+        ..GeneratorOptions::default()
+    };
+    std::fs::write(path, generate(&options))
+}
+
+impl SceneStack {
+    pub fn new(
+        menu: Menu,
+        stream: OutputStreamHandle,
+        mouse: MouseSettings,
+        record_path: Option<String>,
+    ) -> SceneStack {
+        SceneStack {
+            scenes: vec![Scene::Menu(menu)],
+            final_screen: Texture::load("./Assets/FinalScreen.png").expect("Failed to load texture"),
+            stream,
+            mouse,
+            record_path,
+        }
+    }
+
+    /// Updates the top scene with one frame of input. Returns `false` once
+    /// the player quits.
+    pub fn update(&mut self, input: &dyn InputSource, dt: f32) -> bool {
+        let transition = match self.scenes.last_mut().expect("la pila de escenas está vacía") {
+            Scene::Menu(menu) => {
+                let start = |maze_file: &str| {
+                    match Game::new(maze_file, &self.stream, self.mouse.clone(), self.record_path.clone()) {
+                        Ok(game) => Transition::Push(Scene::Playing(Box::new(game))),
+                        Err(error) => {
+                            eprintln!("Error al cargar {}: {}", maze_file, error);
+                            Transition::Stay
+                        }
+                    }
+                };
+
+                if input.is_pressed(Action::Quit) {
+                    Transition::Quit
+                } else {
+                    match menu.update(input) {
+                        Some(MenuAction::Play(maze_file)) => start(&maze_file),
+                        Some(MenuAction::Random) => match generate_random_maze("./maze_random.txt") {
+                            Ok(()) => start("./maze_random.txt"),
+                            Err(error) => {
+                                eprintln!("No se pudo generar el laberinto aleatorio: {}", error);
+                                Transition::Stay
+                            }
+                        },
+                        Some(MenuAction::Quit) => Transition::Quit,
+                        None => Transition::Stay,
+                    }
+                }
+            }
+            Scene::Playing(game) => game.update(input, dt),
+            Scene::Paused => {
+                if input.is_pressed(Action::Pause) || input.is_pressed(Action::Quit) {
+                    Transition::Pop
+                } else if input.is_pressed(Action::Retry) {
+                    Transition::Retry
+                } else if input.is_pressed(Action::Confirm) {
+                    Transition::ToMenu
+                } else {
+                    Transition::Stay
+                }
+            }
+            Scene::Victory { .. } | Scene::Defeat { .. } => {
+                if input.is_pressed(Action::Retry) {
+                    Transition::Retry
+                } else if input.is_pressed(Action::Confirm) || input.is_pressed(Action::Quit) {
+                    Transition::ToMenu
+                } else {
+                    Transition::Stay
+                }
+            }
+        };
+
+        match transition {
+            Transition::Stay => {}
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => {
+                self.scenes.pop();
+                if let Some(Scene::Playing(game)) = self.scenes.last() {
+                    game.resume_music();
+                }
+            }
+            Transition::Retry => {
+                while let Some(scene) = self.scenes.last_mut() {
+                    if let Scene::Playing(game) = scene {
+                        game.restart();
+                        break;
+                    }
+                    self.scenes.pop();
+                }
+            }
+            Transition::ToMenu => self.scenes.truncate(1),
+            Transition::Quit => return false,
+        }
+        true
+    }
+
+    pub fn render(&self, framebuffer: &mut Framebuffer) {
+        let first = self.scenes.iter().rposition(|scene| !scene.is_overlay()).unwrap_or(0);
+
+        for scene in &self.scenes[first..] {
+            match scene {
+                Scene::Menu(menu) => menu.render(framebuffer),
+                Scene::Playing(game) => game.render(framebuffer),
+                Scene::Paused => {
+                    dim(framebuffer);
+                    draw_banner(framebuffer, "Pausa", "P/Esc: continuar   R: reintentar   Enter: menú");
+                }
+                Scene::Defeat { limit } => {
+                    framebuffer.set_blend_mode(BlendMode::Multiply);
+                    framebuffer.set_current_color(0xC04040);
+                    framebuffer.fill();
+                    framebuffer.set_blend_mode(BlendMode::Normal);
+                    let message = format!("¡Se acabó el tiempo!\nLímite: {:.0} s", limit);
+                    draw_banner(framebuffer, &message, "R: reintentar   Enter: menú");
+                }
+                Scene::Victory { elapsed, par } => {
+                    let (width, height) = (framebuffer.width, framebuffer.height);
+                    draw_centered_image(framebuffer, &self.final_screen);
+
+                    let time = match par {
+                        Some(par) => format!("¡Meta! Tiempo: {:.1} s (par: {:.1} s)", elapsed, par),
+                        None => format!("¡Meta! Tiempo: {:.1} s", elapsed),
+                    };
+                    let style = TextStyle {
+                        scale: 2,
+                        align: Align::Center,
+                        max_width: Some(width - 40),
+                        ..TextStyle::default()
+                    };
+                    framebuffer.draw_text(width / 2, height.saturating_sub(90), &time, &style);
+                    framebuffer.draw_text(width / 2, height.saturating_sub(50), "R: reintentar   Enter: menú", &style);
+                }
+            }
+        }
+    }
+
+    /// Title for the window: the level being played, if any.
+    pub fn title(&self) -> &str {
+        self.scenes
+            .iter()
+            .rev()
+            .find_map(|scene| match scene {
+                Scene::Playing(game) => Some(game.title()),
+                _ => None,
+            })
+            .unwrap_or("Proyecto 1")
+    }
+
+    /// Whether the cursor should be hidden for mouse-look.
+    pub fn hides_cursor(&self) -> bool {
+        matches!(self.scenes.last(), Some(Scene::Playing(_))) && self.mouse.mode == MouseMode::Relative
+    }
+}
+
+/// A large centered message with a line of instructions below it.
+fn draw_banner(framebuffer: &mut Framebuffer, message: &str, instructions: &str) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let title = TextStyle {
+        scale: 4,
+        align: Align::Center,
+        max_width: Some(width - 40),
+        ..TextStyle::default()
+    };
+    let title_height = framebuffer.draw_text(width / 2, height / 3, message, &title);
+
+    let hint = TextStyle {
+        scale: 2,
+        align: Align::Center,
+        max_width: Some(width - 40),
+        ..TextStyle::default()
+    };
+    framebuffer.draw_text(width / 2, height / 3 + title_height + 30, instructions, &hint);
+}
+
+/// Halves the brightness of everything drawn so far.
+fn dim(framebuffer: &mut Framebuffer) {
+    framebuffer.set_current_color(0x000000);
+    framebuffer.set_opacity(0.5);
+    framebuffer.fill();
+    framebuffer.set_opacity(1.0);
+}
@@ -0,0 +1,126 @@
+use rodio::Sink;
+
+use crate::bindings::MouseSettings;
+use crate::clock::FixedTimestep;
+use crate::game::{spawn_player, SIMULATION_RATE};
+use crate::input::InputSource;
+use crate::maze::{Level, Maze};
+use crate::player::Player;
+use crate::player_controller::{process_events, MouseLook};
+
+/// The player moved at a fixed rate by whatever input it is fed. The game
+/// and `replay` both run it, so a recording replays exactly as it was played.
+pub struct Simulation {
+    pub player: Player,
+    previous_player: Player,
+    timestep: FixedTimestep,
+    mouse_look: MouseLook,
+}
+
+impl Simulation {
+    pub fn new(level: &Level) -> Simulation {
+        let player = spawn_player(level);
+        Simulation {
+            player,
+            previous_player: player,
+            timestep: FixedTimestep::new(SIMULATION_RATE),
+            mouse_look: MouseLook::new(),
+        }
+    }
+
+    /// Runs the steps that `dt` seconds of the current input frame add up
+    /// to. Returns `true` as soon as a step ends on the goal.
+    pub fn frame(
+        &mut self,
+        input: &dyn InputSource,
+        dt: f32,
+        maze: &Maze,
+        mouse: &MouseSettings,
+        walking_sound: Option<&Sink>,
+    ) -> bool {
+        self.timestep.advance(dt);
+        self.mouse_look.sample(input);
+
+        while self.timestep.next_step() {
+            self.previous_player = self.player;
+            process_events(input, &mut self.player, maze, mouse, &mut self.mouse_look, walking_sound, self.timestep.step());
+
+            let (player_col, player_row) = maze.cell_coords(self.player.pos);
+            if maze.is_goal(player_col, player_row) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The player between the last two steps, for rendering.
+    pub fn view(&self) -> Player {
+        self.previous_player.lerp(&self.player, self.timestep.alpha())
+    }
+
+    /// Simulated seconds so far.
+    pub fn time(&self) -> f32 {
+        self.timestep.time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{Action, MouseMode};
+    use crate::input::{InputFrame, ScriptedInput};
+    use crate::maze::{load_maze, parse_maze};
+
+    fn mouse() -> MouseSettings {
+        MouseSettings { mode: MouseMode::Relative, sensitivity: 0.004, invert: false }
+    }
+
+    fn holding(actions: &[Action], frame_times: &[f32]) -> ScriptedInput {
+        let frames = frame_times
+            .iter()
+            .map(|&dt| InputFrame {
+                dt,
+                down: actions.to_vec(),
+                ..InputFrame::default()
+            })
+            .collect();
+        ScriptedInput::new(frames)
+    }
+
+    /// Runs frames of the given lengths with the same keys held throughout.
+    fn simulate(frame_times: &[f32]) -> (Player, f32) {
+        let level = load_maze("./maze_hard.txt", 55).unwrap();
+        let mut input = holding(&[Action::MoveForward, Action::TurnRight, Action::Sprint], frame_times);
+        let mut simulation = Simulation::new(&level);
+        while input.advance() {
+            simulation.frame(&input, input.frame().dt, &level.maze, &mouse(), None);
+        }
+        (simulation.player, simulation.time())
+    }
+
+    #[test]
+    fn frame_rate_does_not_change_the_simulation() {
+        // Half a step past a whole number of steps, so rounding in how the
+        // frames add up cannot change the step count.
+        let total = 4.0 + 0.5 / SIMULATION_RATE;
+        let even = vec![total / 240.0; 240];
+        let uneven: Vec<f32> = (0..50).map(|i| if i % 2 == 0 { 0.3 } else { 1.7 } * total / 50.0).collect();
+
+        let (player, time) = simulate(&even);
+        assert_eq!(time, 240.0 / SIMULATION_RATE);
+        assert_eq!(simulate(&uneven), (player, time));
+    }
+
+    #[test]
+    fn frame_stops_on_the_step_that_reaches_the_goal() {
+        let level = parse_maze("+----+\n|@  F|\n+----+", 55).unwrap();
+        let mut input = holding(&[Action::MoveForward], &[5.0]);
+        input.advance();
+
+        let mut simulation = Simulation::new(&level);
+        assert!(simulation.frame(&input, 5.0, &level.maze, &mouse(), None));
+        let (col, row) = level.maze.cell_coords(simulation.player.pos);
+        assert!(level.maze.is_goal(col, row));
+        assert!(simulation.time() < 5.0);
+    }
+}
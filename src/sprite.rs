@@ -0,0 +1,170 @@
+use nalgebra_glm::Vec2;
+use std::f32::consts::PI;
+
+use crate::framebuffer::{BlitOptions, Framebuffer, Rect};
+use crate::maze::Maze;
+use crate::player::Player;
+use crate::texture::Texture;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpriteKind {
+    Coin,
+}
+
+impl SpriteKind {
+    pub fn from_char(c: char) -> Option<SpriteKind> {
+        match c {
+            'C' => Some(SpriteKind::Coin),
+            _ => None,
+        }
+    }
+
+    // Both as a fraction of the block size.
+    fn size(self) -> f32 {
+        match self {
+            SpriteKind::Coin => 0.4,
+        }
+    }
+
+    fn height(self) -> f32 {
+        match self {
+            SpriteKind::Coin => 0.35,
+        }
+    }
+}
+
+pub struct Sprite {
+    pub pos: Vec2,
+    pub kind: SpriteKind,
+}
+
+pub struct SpriteTextures {
+    pub coin: Texture,
+}
+
+impl SpriteTextures {
+    pub fn load() -> SpriteTextures {
+        SpriteTextures {
+            coin: Texture::load("./Assets/coin.png").expect("Failed to load texture"),
+        }
+    }
+
+    fn get(&self, kind: SpriteKind) -> &Texture {
+        match kind {
+            SpriteKind::Coin => &self.coin,
+        }
+    }
+}
+
+/// Removes sprite markers from the maze, leaving an open cell behind, and
+/// returns a sprite centered in each of those cells.
+pub fn extract_sprites(maze: &mut Maze) -> Vec<Sprite> {
+    let markers: Vec<(usize, usize, SpriteKind)> = maze
+        .cells()
+        .filter_map(|(col, row, cell)| SpriteKind::from_char(cell).map(|kind| (col, row, kind)))
+        .collect();
+
+    markers
+        .into_iter()
+        .map(|(col, row, kind)| {
+            maze.set_cell(col, row, ' ');
+            Sprite {
+                pos: maze.cell_center(col, row),
+                kind,
+            }
+        })
+        .collect()
+}
+
+pub fn render_sprites(
+    framebuffer: &mut Framebuffer,
+    player: &Player,
+    sprites: &[Sprite],
+    textures: &SpriteTextures,
+    depth_buffer: &[f32],
+    block_size: usize,
+    fisheye: bool,
+) {
+    let width = framebuffer.width as f32;
+    let horizon = player.horizon(framebuffer.height);
+    let block = block_size as f32;
+    let eye = player.eye_height(block_size);
+    let half_fov = player.fov / 2.0;
+
+    let mut visible: Vec<(f32, f32, &Sprite)> = sprites
+        .iter()
+        .filter_map(|sprite| {
+            let delta = sprite.pos - player.pos;
+            let mut angle = delta.y.atan2(delta.x) - player.a;
+            angle = (angle + PI).rem_euclid(2.0 * PI) - PI;
+
+            // Let sprites half outside the field of view still be drawn.
+            if angle.abs() > half_fov + 0.3 {
+                return None;
+            }
+
+            let distance = delta.magnitude();
+            let depth = if fisheye { distance } else { distance * angle.cos() };
+            if depth < 1.0 {
+                return None;
+            }
+
+            let current_ray = if fisheye {
+                (angle + half_fov) / player.fov
+            } else {
+                (angle.tan() / half_fov.tan() + 1.0) / 2.0
+            };
+
+            Some((depth, current_ray * width, sprite))
+        })
+        .collect();
+
+    visible.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    for (depth, screen_x, sprite) in visible {
+        let texture = textures.get(sprite.kind);
+
+        let size = (sprite.kind.size() * block / depth) * 200.0;
+        let center_y = horizon + ((eye - sprite.kind.height() * block) / depth) * 200.0;
+
+        let x0 = screen_x - size / 2.0;
+        let y0 = center_y - size / 2.0;
+
+        let start_x = x0.max(0.0) as usize;
+        let end_x = ((x0 + size) as usize).min(framebuffer.width);
+
+        // One texel column per screen column, so walls in front can hide
+        // part of the sprite.
+        for (x, &wall_depth) in depth_buffer.iter().enumerate().take(end_x).skip(start_x) {
+            if depth >= wall_depth {
+                continue;
+            }
+
+            let u = (x as f32 - x0) / size;
+            let column = Rect::new((u * texture.width as f32) as isize, 0, 1, texture.height);
+            let strip = Rect::new(x as isize, y0.floor() as isize, 1, size as usize);
+            framebuffer.blit(texture, column, strip, &BlitOptions::default());
+        }
+    }
+}
+
+/// Draws every sprite as a flat icon on a top-down view where one world unit
+/// maps to `1 / scale` pixels.
+pub fn render_sprite_icons(
+    framebuffer: &mut Framebuffer,
+    sprites: &[Sprite],
+    textures: &SpriteTextures,
+    block_size: usize,
+    scale: usize,
+) {
+    let size = ((block_size as f32 * 0.5) / scale as f32).max(1.0);
+
+    for sprite in sprites {
+        let texture = textures.get(sprite.kind);
+        let x0 = sprite.pos.x / scale as f32 - size / 2.0;
+        let y0 = sprite.pos.y / scale as f32 - size / 2.0;
+
+        let icon = Rect::new(x0 as isize, y0 as isize, size as usize, size as usize);
+        framebuffer.blit(texture, texture.bounds(), icon, &BlitOptions::default());
+    }
+}
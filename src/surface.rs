@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::texture::Texture;
+
+#[derive(Default)]
+pub struct SurfaceOverride {
+    pub floor: Option<Rc<Texture>>,
+    pub ceiling: Option<Rc<Texture>>,
+}
+
+/// Floor and ceiling textures for the 3D view. Cells listed in `overrides`
+/// replace the defaults; a missing ceiling is painted with `sky_color`.
+pub struct Surfaces {
+    pub floor: Rc<Texture>,
+    pub ceiling: Option<Rc<Texture>>,
+    pub sky_color: u32,
+    pub overrides: HashMap<char, SurfaceOverride>,
+}
+
+impl Surfaces {
+    pub fn floor_at(&self, cell: char) -> &Texture {
+        self.overrides
+            .get(&cell)
+            .and_then(|o| o.floor.as_deref())
+            .unwrap_or(&self.floor)
+    }
+
+    pub fn ceiling_at(&self, cell: char) -> Option<&Texture> {
+        self.overrides
+            .get(&cell)
+            .and_then(|o| o.ceiling.as_deref())
+            .or(self.ceiling.as_deref())
+    }
+}
@@ -0,0 +1,282 @@
+use image::GenericImageView;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::framebuffer::Rect;
+use crate::surface::Surfaces;
+
+pub struct Texture {
+    pub data: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Texture {
+    pub fn load(path: &str) -> Result<Texture, image::ImageError> {
+        let img = image::open(path)?;
+        let (width, height) = img.dimensions();
+        let data = img.to_rgba8().into_raw();
+        let data: Vec<u32> = data.chunks(4).map(|p| {
+            let r = p[0] as u32;
+            let g = p[1] as u32;
+            let b = p[2] as u32;
+            let a = p[3] as u32;
+            (a << 24) | (r << 16) | (g << 8) | b
+        }).collect();
+
+        Ok(Texture {
+            data,
+            width: width as usize,
+            height: height as usize,
+        })
+    }
+
+    pub fn solid(color: u32) -> Texture {
+        Texture {
+            data: vec![color],
+            width: 1,
+            height: 1,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        self.data[y * self.width + x]
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// Blends the four texels around `(x, y)`, in texel coordinates where
+    /// texel centers sit on whole numbers.
+    pub fn bilinear(&self, x: f32, y: f32) -> u32 {
+        let (x, y) = (x.max(0.0), y.max(0.0));
+        let (x0, y0) = (x as usize, y as usize);
+        let (fx, fy) = (x.fract(), y.fract());
+
+        let corners = [
+            (self.get(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (self.get(x0 + 1, y0), fx * (1.0 - fy)),
+            (self.get(x0, y0 + 1), (1.0 - fx) * fy),
+            (self.get(x0 + 1, y0 + 1), fx * fy),
+        ];
+        let channel = |shift: u32| {
+            let value: f32 = corners.iter().map(|&(texel, weight)| ((texel >> shift) & 0xFF) as f32 * weight).sum();
+            (value.round() as u32).min(255) << shift
+        };
+        channel(24) | channel(16) | channel(8) | channel(0)
+    }
+
+    pub fn sample(&self, u: f32, v: f32) -> u32 {
+        let x = (u * self.width as f32).max(0.0) as usize;
+        let y = (v * self.height as f32).max(0.0) as usize;
+        self.get(x, y)
+    }
+}
+
+enum Slot {
+    Default,
+    Floor,
+    Ceiling,
+    Cell(char),
+    CellFloor(char),
+    CellCeiling(char),
+}
+
+/// Textures for every maze character, declared in a `<maze>.textures` file
+/// next to the maze. Each image is loaded once, however many keys use it.
+///
+/// ```text
+/// # key = path, relative to the config file
+/// default = ./Assets/prueba2.jpg
+/// F = ./Assets/marioflag.png
+/// floor = ./Assets/grass.jpg
+/// sky = 0x03a9f4
+/// b.floor = ./bricks.png
+/// ```
+pub struct TextureRegistry {
+    cells: HashMap<char, Rc<Texture>>,
+    default_cell: Rc<Texture>,
+    pub surfaces: Surfaces,
+}
+
+const DEFAULT_CELL: &str = "./Assets/prueba2.jpg";
+const DEFAULT_FLAG: &str = "./Assets/marioflag.png";
+const DEFAULT_FLOOR: &str = "./Assets/grass.jpg";
+
+/// Stands in for a built-in image that is missing from the assets.
+const MISSING_COLOR: u32 = 0xFFFF00FF;
+
+impl TextureRegistry {
+    /// Entries that fail to load are reported and left to the defaults, which
+    /// are only loaded for the slots the config does not fill.
+    pub fn for_maze(maze_file: &str) -> TextureRegistry {
+        let config_path = Path::new(maze_file).with_extension("textures");
+        let base = config_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let mut loaded = HashMap::new();
+        let mut registry = TextureRegistry {
+            cells: HashMap::new(),
+            default_cell: Rc::new(Texture::solid(MISSING_COLOR)),
+            surfaces: Surfaces {
+                floor: Rc::new(Texture::solid(MISSING_COLOR)),
+                ceiling: None,
+                sky_color: 0x03a9f4,
+                overrides: HashMap::new(),
+            },
+        };
+
+        let (default_set, floor_set) = match fs::read_to_string(&config_path) {
+            Ok(config) => registry.apply_config(&config, &base, &config_path, &mut loaded),
+            Err(_) => {
+                println!("No se encontró {}, usando texturas por defecto", config_path.display());
+                (false, false)
+            }
+        };
+        let builtin = |loaded: &mut HashMap<PathBuf, Rc<Texture>>, path: &str| {
+            Self::load_once(loaded, Path::new("."), path).unwrap_or_else(|error| {
+                println!("No se pudo cargar {}: {}", path, error);
+                Rc::new(Texture::solid(MISSING_COLOR))
+            })
+        };
+        if !default_set {
+            registry.default_cell = builtin(&mut loaded, DEFAULT_CELL);
+        }
+        if !floor_set {
+            registry.surfaces.floor = builtin(&mut loaded, DEFAULT_FLOOR);
+        }
+        registry.cells.entry('F').or_insert_with(|| builtin(&mut loaded, DEFAULT_FLAG));
+
+        registry
+    }
+
+    fn load_once(
+        loaded: &mut HashMap<PathBuf, Rc<Texture>>,
+        base: &Path,
+        path: &str,
+    ) -> Result<Rc<Texture>, image::ImageError> {
+        let path: PathBuf = base.join(path).components().collect();
+        if let Some(texture) = loaded.get(&path) {
+            return Ok(texture.clone());
+        }
+        let texture = Rc::new(Texture::load(path.to_str().unwrap_or_default())?);
+        loaded.insert(path, texture.clone());
+        Ok(texture)
+    }
+
+    /// Returns whether the config set the default cell and the floor.
+    fn apply_config(
+        &mut self,
+        config: &str,
+        base: &Path,
+        config_path: &Path,
+        loaded: &mut HashMap<PathBuf, Rc<Texture>>,
+    ) -> (bool, bool) {
+        let (mut default_set, mut floor_set) = (false, false);
+        for (line_number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                println!("{}:{}: se esperaba `clave = valor`", config_path.display(), line_number + 1);
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            if key == "sky" {
+                match u32::from_str_radix(value.trim_start_matches("0x"), 16) {
+                    Ok(color) => self.surfaces.sky_color = color,
+                    Err(_) => println!("{}:{}: color inválido `{}`", config_path.display(), line_number + 1, value),
+                }
+                continue;
+            }
+
+            let (cell, slot) = match key.split_once('.') {
+                Some((cell, slot)) => (cell, slot),
+                None => (key, ""),
+            };
+            let mut chars = cell.chars();
+            let cell = match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => None,
+            };
+
+            let slot = match (cell, slot) {
+                (None, "") if key == "default" => Slot::Default,
+                (None, "") if key == "floor" => Slot::Floor,
+                (None, "") if key == "ceiling" => Slot::Ceiling,
+                (Some(c), "") => Slot::Cell(c),
+                (Some(c), "floor") => Slot::CellFloor(c),
+                (Some(c), "ceiling") => Slot::CellCeiling(c),
+                _ => {
+                    println!("{}:{}: clave desconocida `{}`", config_path.display(), line_number + 1, key);
+                    continue;
+                }
+            };
+
+            let texture = match Self::load_once(loaded, base, value) {
+                Ok(texture) => texture,
+                Err(error) => {
+                    println!(
+                        "{}:{}: no se pudo cargar `{}` ({}), se mantiene la textura por defecto",
+                        config_path.display(),
+                        line_number + 1,
+                        value,
+                        error
+                    );
+                    continue;
+                }
+            };
+            match slot {
+                Slot::Default => {
+                    self.default_cell = texture;
+                    default_set = true;
+                }
+                Slot::Floor => {
+                    self.surfaces.floor = texture;
+                    floor_set = true;
+                }
+                Slot::Ceiling => self.surfaces.ceiling = Some(texture),
+                Slot::Cell(c) => {
+                    self.cells.insert(c, texture);
+                }
+                Slot::CellFloor(c) => {
+                    self.surfaces.overrides.entry(c).or_default().floor = Some(texture);
+                }
+                Slot::CellCeiling(c) => {
+                    self.surfaces.overrides.entry(c).or_default().ceiling = Some(texture);
+                }
+            }
+        }
+        (default_set, floor_set)
+    }
+
+    pub fn cell(&self, cell: char) -> &Texture {
+        self.cells.get(&cell).unwrap_or(&self.default_cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_entries_keep_the_default_texture() {
+        let dir = std::env::temp_dir().join(format!("texture_registry_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("level.textures"), "F = ./missing.png\nfloor = ./missing.png\n").unwrap();
+
+        let registry = TextureRegistry::for_maze(dir.join("level.txt").to_str().unwrap());
+        let flag = Texture::load(DEFAULT_FLAG).unwrap();
+        let floor = Texture::load(DEFAULT_FLOOR).unwrap();
+        assert_eq!(registry.cell('F').data, flag.data);
+        assert_eq!(registry.surfaces.floor.data, floor.data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}